@@ -0,0 +1,630 @@
+// Mappers swap which page of a larger ROM or RAM image is visible through a window of the
+// address space, usually when a control register is written
+
+use std::any::Any;
+
+/// Sits between `Memory` and its RAM, banking images in and out of the address space
+pub trait Mapper: Any {
+    /// The byte the mapper has at `addr`, or `None` to read RAM. `io_pins` are the levels on
+    /// the 6510's I/O port pins, if there is one
+    fn read(&self, addr: u16, io_pins: Option<u8>) -> Option<u8>;
+    /// Sees every write. Returns `true` if the write was taken by the mapper, so it shouldn't
+    /// reach RAM
+    fn write(&mut self, addr: u16, byte: u8, io_pins: Option<u8>) -> bool;
+    /// Whether writes to `addr` are dropped because the mapper has ROM there, or `None` if the
+    /// mapper has nothing there. Registers aren't read-only, even when they're over ROM
+    fn is_read_only(&self, addr: u16, io_pins: Option<u8>) -> Option<bool>;
+    /// The mapper's registers and the contents of any RAM it holds, for save states
+    fn save_state(&self) -> Vec<u8>;
+    /// Restores a state from `save_state`. Returns `false`, leaving the mapper unchanged, if
+    /// the state didn't come from this kind of mapper
+    fn load_state(&mut self, state: &[u8]) -> bool;
+}
+
+/// Takes the next `length` bytes of a mapper's saved state
+fn take<'a>(state: &mut &'a [u8], length: usize) -> Option<&'a [u8]> {
+    let (taken, rest) = state.split_at_checked(length)?;
+    *state = rest;
+    Some(taken)
+}
+
+/// Restores `mapper` from `state` with `restore`, only keeping the result if all of `state`
+/// was used without a problem
+fn load_whole<M: Clone>(
+    mapper: &mut M,
+    mut state: &[u8],
+    restore: impl FnOnce(&mut M, &mut &[u8]) -> Option<()>,
+) -> bool {
+    let mut loaded = mapper.clone();
+    if restore(&mut loaded, &mut state).is_some() && state.is_empty() {
+        *mapper = loaded;
+        true
+    } else {
+        false
+    }
+}
+
+/// Size of the pages swapped through a window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BankSize {
+    Size8K,
+    Size16K,
+}
+
+impl BankSize {
+    pub const fn bytes(self) -> usize {
+        match self {
+            BankSize::Size8K => 0x2000,
+            BankSize::Size16K => 0x4000,
+        }
+    }
+}
+
+/// A window onto one page of an image at a time
+#[derive(Clone, Debug)]
+pub struct BankedWindow {
+    start: u16,
+    size: BankSize,
+    image: Vec<u8>,
+    selected: usize,
+    writable: bool,
+}
+
+impl BankedWindow {
+    /// A window at `start` showing pages of `image`, starting with the first. Only RAM
+    /// windows are `writable`; writes to ROM windows are dropped
+    pub fn new(start: u16, size: BankSize, image: Vec<u8>, writable: bool) -> Self {
+        assert!(
+            !image.is_empty() && image.len().is_multiple_of(size.bytes()),
+            "image must be made of whole banks"
+        );
+        BankedWindow {
+            start,
+            size,
+            image,
+            selected: 0,
+            writable,
+        }
+    }
+    pub fn bank_count(&self) -> usize {
+        self.image.len() / self.size.bytes()
+    }
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+    /// Shows `bank` through the window. Banks past the end of the image wrap around, as only
+    /// the low bits of the bank number are wired up
+    pub fn select(&mut self, bank: usize) {
+        self.selected = bank % self.bank_count();
+    }
+    /// Appends the selected bank, and the image if it's RAM
+    fn save(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&(self.selected as u32).to_le_bytes());
+        if self.writable {
+            state.extend_from_slice(&self.image);
+        }
+    }
+    fn restore(&mut self, state: &mut &[u8]) -> Option<()> {
+        let selected = u32::from_le_bytes(take(state, 4)?.try_into().ok()?) as usize;
+        if selected >= self.bank_count() {
+            return None;
+        }
+        self.selected = selected;
+        if self.writable {
+            let image = take(state, self.image.len())?;
+            self.image.copy_from_slice(image);
+        }
+        Some(())
+    }
+    pub fn contains(&self, addr: u16) -> bool {
+        let offset = addr.wrapping_sub(self.start) as usize;
+        addr >= self.start && offset < self.size.bytes()
+    }
+    fn offset(&self, addr: u16) -> usize {
+        self.selected * self.size.bytes() + (addr - self.start) as usize
+    }
+    pub fn read(&self, addr: u16) -> Option<u8> {
+        self.contains(addr).then(|| self.image[self.offset(addr)])
+    }
+    /// Whether writes to `addr` are dropped, or `None` if it isn't in the window
+    pub fn is_read_only(&self, addr: u16) -> Option<bool> {
+        self.contains(addr).then_some(!self.writable)
+    }
+    /// Returns whether `addr` is in the window
+    pub fn write(&mut self, addr: u16, byte: u8) -> bool {
+        if !self.contains(addr) {
+            return false;
+        }
+        if self.writable {
+            let offset = self.offset(addr);
+            self.image[offset] = byte;
+        }
+        true
+    }
+}
+
+/// Any number of 8K or 16K windows, each with a register that selects its bank when written
+#[derive(Clone, Debug, Default)]
+pub struct WindowMapper {
+    windows: Vec<BankedWindow>,
+    // Address of each window's bank select register
+    registers: Vec<u16>,
+}
+
+impl WindowMapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a window which switches banks when `register` is written, returning its index.
+    /// A register inside a ROM window takes the write instead of it being dropped
+    pub fn add_window(&mut self, window: BankedWindow, register: u16) -> usize {
+        self.windows.push(window);
+        self.registers.push(register);
+        self.windows.len() - 1
+    }
+    pub fn window(&self, index: usize) -> &BankedWindow {
+        &self.windows[index]
+    }
+    pub fn window_mut(&mut self, index: usize) -> &mut BankedWindow {
+        &mut self.windows[index]
+    }
+}
+
+impl Mapper for WindowMapper {
+    fn read(&self, addr: u16, _io_pins: Option<u8>) -> Option<u8> {
+        self.windows.iter().find_map(|window| window.read(addr))
+    }
+    fn write(&mut self, addr: u16, byte: u8, _io_pins: Option<u8>) -> bool {
+        let mut taken = false;
+        for (window, &register) in self.windows.iter_mut().zip(&self.registers) {
+            if register == addr {
+                window.select(byte as usize);
+                taken = true;
+            }
+        }
+        taken
+            || self
+                .windows
+                .iter_mut()
+                .any(|window| window.write(addr, byte))
+    }
+    fn is_read_only(&self, addr: u16, _io_pins: Option<u8>) -> Option<bool> {
+        if self.registers.contains(&addr) {
+            return Some(false);
+        }
+        self.windows
+            .iter()
+            .find_map(|window| window.is_read_only(addr))
+    }
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        for window in &self.windows {
+            window.save(&mut state);
+        }
+        state
+    }
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        load_whole(self, state, |mapper, state| {
+            mapper
+                .windows
+                .iter_mut()
+                .try_for_each(|window| window.restore(state))
+        })
+    }
+}
+
+/// NES mapper 2. A switchable 16K PRG bank at $8000, with the last bank fixed at $C000.
+/// Writing anywhere in $8000-$FFFF selects the bank
+#[derive(Clone, Debug)]
+pub struct UxRom {
+    switchable: BankedWindow,
+    fixed: BankedWindow,
+}
+
+impl UxRom {
+    pub fn new(prg: Vec<u8>) -> Self {
+        let switchable = BankedWindow::new(0x8000, BankSize::Size16K, prg, false);
+        let mut fixed = switchable.clone();
+        fixed.start = 0xC000;
+        fixed.select(fixed.bank_count() - 1);
+        UxRom { switchable, fixed }
+    }
+    pub fn bank(&self) -> usize {
+        self.switchable.selected()
+    }
+}
+
+impl Mapper for UxRom {
+    fn read(&self, addr: u16, _io_pins: Option<u8>) -> Option<u8> {
+        self.switchable.read(addr).or_else(|| self.fixed.read(addr))
+    }
+    fn write(&mut self, addr: u16, byte: u8, _io_pins: Option<u8>) -> bool {
+        if addr < 0x8000 {
+            return false;
+        }
+        self.switchable.select(byte as usize);
+        true
+    }
+    /// All of the ROM is covered by the bank register
+    fn is_read_only(&self, addr: u16, _io_pins: Option<u8>) -> Option<bool> {
+        (addr >= 0x8000).then_some(false)
+    }
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = Vec::new();
+        self.switchable.save(&mut state);
+        state
+    }
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        load_whole(self, state, |mapper, state| {
+            mapper.switchable.restore(state)
+        })
+    }
+}
+
+/// NES mapper 1, as seen by the CPU. Registers are loaded a bit at a time through a serial
+/// shift register, and there's 8K of PRG RAM at $6000. CHR banking is left to the PPU side
+#[derive(Clone, Debug)]
+pub struct Mmc1 {
+    prg: Vec<u8>,
+    prg_ram: Vec<u8>,
+    // Bit 4 is a marker which reaches bit 0 once five bits have been written
+    shift: u8,
+    control: u8,
+    chr_banks: [u8; 2],
+    prg_bank: u8,
+}
+
+impl Mmc1 {
+    pub fn new(prg: Vec<u8>) -> Self {
+        assert!(
+            !prg.is_empty() && prg.len().is_multiple_of(0x4000),
+            "PRG ROM must be made of whole 16K banks"
+        );
+        Mmc1 {
+            prg,
+            prg_ram: vec![0; 0x2000],
+            shift: 0b10000,
+            // Powers on with the last bank fixed at $C000
+            control: 0b01100,
+            chr_banks: [0; 2],
+            prg_bank: 0,
+        }
+    }
+    pub fn control(&self) -> u8 {
+        self.control
+    }
+    pub fn chr_banks(&self) -> [u8; 2] {
+        self.chr_banks
+    }
+    pub fn prg_bank(&self) -> u8 {
+        self.prg_bank
+    }
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0b10000 == 0
+    }
+    // Which 16K bank is visible at $8000 or $C000
+    fn bank_at(&self, addr: u16) -> usize {
+        let bank = (self.prg_bank & 0b01111) as usize;
+        let last = self.prg.len() / 0x4000 - 1;
+        let high = addr >= 0xC000;
+        let bank = match (self.control >> 2) & 0b11 {
+            // 32K mode ignores the low bit of the bank number
+            0 | 1 => (bank & !1) + high as usize,
+            2 if high => bank,
+            2 => 0,
+            _ if high => last,
+            _ => bank,
+        };
+        bank % (last + 1)
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn read(&self, addr: u16, _io_pins: Option<u8>) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Some(self.prg_ram[(addr - 0x6000) as usize])
+            }
+            0x8000..=0xFFFF => {
+                let offset = self.bank_at(addr) * 0x4000 + (addr & 0x3FFF) as usize;
+                Some(self.prg[offset])
+            }
+            _ => None,
+        }
+    }
+    fn write(&mut self, addr: u16, byte: u8, _io_pins: Option<u8>) -> bool {
+        match addr {
+            0x6000..=0x7FFF => {
+                if self.prg_ram_enabled() {
+                    self.prg_ram[(addr - 0x6000) as usize] = byte;
+                }
+                true
+            }
+            0x8000..=0xFFFF => {
+                if byte & 0b10000000 != 0 {
+                    // Resetting also goes back to fixing the last bank at $C000
+                    self.shift = 0b10000;
+                    self.control |= 0b01100;
+                    return true;
+                }
+                let full = self.shift & 1 != 0;
+                self.shift = (self.shift >> 1) | ((byte & 1) << 4);
+                if full {
+                    let value = self.shift;
+                    match addr {
+                        0x8000..=0x9FFF => self.control = value,
+                        0xA000..=0xBFFF => self.chr_banks[0] = value,
+                        0xC000..=0xDFFF => self.chr_banks[1] = value,
+                        _ => self.prg_bank = value,
+                    }
+                    self.shift = 0b10000;
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+    /// PRG RAM, or the registers covering all of PRG ROM
+    fn is_read_only(&self, addr: u16, _io_pins: Option<u8>) -> Option<bool> {
+        (addr >= 0x6000).then_some(false)
+    }
+    fn save_state(&self) -> Vec<u8> {
+        let mut state = vec![
+            self.shift,
+            self.control,
+            self.chr_banks[0],
+            self.chr_banks[1],
+            self.prg_bank,
+        ];
+        state.extend_from_slice(&self.prg_ram);
+        state
+    }
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        load_whole(self, state, |mapper, state| {
+            let [shift, control, chr0, chr1, prg_bank] = take(state, 5)?.try_into().ok()?;
+            // The marker bit is always somewhere in the shift register
+            if shift == 0 || shift > 0b11111 {
+                return None;
+            }
+            mapper.shift = shift;
+            mapper.control = control;
+            mapper.chr_banks = [chr0, chr1];
+            mapper.prg_bank = prg_bank;
+            let prg_ram = take(state, mapper.prg_ram.len())?;
+            mapper.prg_ram.copy_from_slice(prg_ram);
+            Some(())
+        })
+    }
+}
+
+/// LORAM, HIRAM and CHAREN, on bits 0 to 2 of the 6510's I/O port
+const LORAM: u8 = 0b001;
+const HIRAM: u8 = 0b010;
+const CHAREN: u8 = 0b100;
+
+/// The C64's PLA, banking the BASIC, KERNAL and character ROMs in and out of RAM according to
+/// the I/O port. Writes always go to the RAM underneath. Cartridges aren't modelled, and the
+/// I/O area at $D000 reads RAM, so devices should be mapped over it
+#[derive(Clone, Debug)]
+pub struct C64Pla {
+    basic: Vec<u8>,
+    kernal: Vec<u8>,
+    chargen: Vec<u8>,
+}
+
+impl C64Pla {
+    /// `basic` and `kernal` are the 8K ROMs, and `chargen` the 4K character ROM
+    pub fn new(basic: Vec<u8>, kernal: Vec<u8>, chargen: Vec<u8>) -> Self {
+        assert_eq!(basic.len(), 0x2000, "BASIC ROM must be 8K");
+        assert_eq!(kernal.len(), 0x2000, "KERNAL ROM must be 8K");
+        assert_eq!(chargen.len(), 0x1000, "character ROM must be 4K");
+        C64Pla {
+            basic,
+            kernal,
+            chargen,
+        }
+    }
+}
+
+impl Mapper for C64Pla {
+    fn read(&self, addr: u16, io_pins: Option<u8>) -> Option<u8> {
+        // Without a port, everything is banked in as after a reset
+        let pins = io_pins.unwrap_or(0xFF);
+        let loram = pins & LORAM != 0;
+        let hiram = pins & HIRAM != 0;
+        match addr {
+            0xA000..=0xBFFF if loram && hiram => Some(self.basic[(addr - 0xA000) as usize]),
+            0xD000..=0xDFFF if (loram || hiram) && pins & CHAREN == 0 => {
+                Some(self.chargen[(addr - 0xD000) as usize])
+            }
+            0xE000..=0xFFFF if hiram => Some(self.kernal[(addr - 0xE000) as usize]),
+            _ => None,
+        }
+    }
+    fn write(&mut self, _addr: u16, _byte: u8, _io_pins: Option<u8>) -> bool {
+        false
+    }
+    /// Writes always reach the RAM underneath the ROMs
+    fn is_read_only(&self, _addr: u16, _io_pins: Option<u8>) -> Option<bool> {
+        None
+    }
+    /// The PLA has no registers of its own, as it follows the I/O port
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    fn load_state(&mut self, state: &[u8]) -> bool {
+        state.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io_port::{DATA_REGISTER, DIRECTION_REGISTER, IoPort},
+        memory::Memory,
+        processor::{Processor, StopReason},
+    };
+
+    /// An image of `count` banks of `size`, with each byte holding its bank number
+    fn numbered_banks(size: BankSize, count: usize) -> Vec<u8> {
+        (0..count)
+            .flat_map(|bank| vec![bank as u8; size.bytes()])
+            .collect()
+    }
+
+    #[test]
+    fn window_mapper_switches_banks() {
+        let mut mapper = WindowMapper::new();
+        let rom = BankedWindow::new(
+            0x8000,
+            BankSize::Size8K,
+            numbered_banks(BankSize::Size8K, 4),
+            false,
+        );
+        let ram = BankedWindow::new(0x6000, BankSize::Size8K, vec![0; 0x4000], true);
+        let rom = mapper.add_window(rom, 0x5000);
+        let ram = mapper.add_window(ram, 0x5001);
+        let mut memory = Memory::new();
+        memory.attach_mapper(mapper);
+        assert_eq!(memory.read_byte(0x8000), 0);
+        memory.write_byte(0x5000, 6);
+        assert_eq!(memory.read_byte(0x9FFF), 2);
+        assert_eq!(memory.read_byte(0xA000), 0);
+        // ROM ignores writes, while RAM banks keep their own contents
+        memory.write_byte(0x8000, 0x55);
+        assert_eq!(memory.read_byte(0x8000), 2);
+        memory.write_byte(0x6000, 0xAA);
+        memory.write_byte(0x5001, 1);
+        assert_eq!(memory.read_byte(0x6000), 0);
+        memory.write_byte(0x5001, 0);
+        assert_eq!(memory.read_byte(0x6000), 0xAA);
+        let mapper = memory.mapper::<WindowMapper>().unwrap();
+        assert_eq!(mapper.window(rom).selected(), 2);
+        assert_eq!(mapper.window(ram).selected(), 0);
+    }
+
+    #[test]
+    fn uxrom_fixes_last_bank() {
+        let mut memory = Memory::new();
+        memory.attach_mapper(UxRom::new(numbered_banks(BankSize::Size16K, 8)));
+        assert_eq!(memory.read_byte(0x8000), 0);
+        assert_eq!(memory.read_byte(0xFFFF), 7);
+        memory.write_byte(0xC123, 3);
+        assert_eq!(memory.read_byte(0xBFFF), 3);
+        assert_eq!(memory.read_byte(0xC000), 7);
+        assert_eq!(memory.mapper::<UxRom>().unwrap().bank(), 3);
+    }
+
+    #[test]
+    fn program_switches_uxrom_banks() {
+        let mut prg = numbered_banks(BankSize::Size16K, 4);
+        // In the fixed bank at $C000: LDA #$02; STA $8000; LDA $8000; BRK
+        prg[0xC000..0xC009]
+            .copy_from_slice(&[0xA9, 0x02, 0x8D, 0x00, 0x80, 0xAD, 0x00, 0x80, 0x00]);
+        let mut memory = Memory::new();
+        memory.attach_mapper(UxRom::new(prg));
+        let mut processor = Processor::with_start_address(memory, 0xC000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.a(), 2);
+    }
+
+    /// Writes `value` to an MMC1 register a bit at a time
+    fn mmc1_write(memory: &mut Memory, addr: u16, value: u8) {
+        for bit in 0..5 {
+            memory.write_byte(addr, (value >> bit) & 1);
+        }
+    }
+
+    #[test]
+    fn mmc1_banks_prg() {
+        let mut memory = Memory::new();
+        memory.attach_mapper(Mmc1::new(numbered_banks(BankSize::Size16K, 8)));
+        assert_eq!(memory.read_byte(0xC000), 7);
+        mmc1_write(&mut memory, 0xE000, 5);
+        assert_eq!(memory.read_byte(0x8000), 5);
+        assert_eq!(memory.read_byte(0xC000), 7);
+        // Fix the first bank at $8000 instead
+        mmc1_write(&mut memory, 0x8000, 0b01000);
+        assert_eq!(memory.read_byte(0x8000), 0);
+        assert_eq!(memory.read_byte(0xC000), 5);
+        // 32K mode
+        mmc1_write(&mut memory, 0x8000, 0b00000);
+        assert_eq!(memory.read_byte(0x8000), 4);
+        assert_eq!(memory.read_byte(0xC000), 5);
+        mmc1_write(&mut memory, 0xA000, 0b10101);
+        assert_eq!(memory.mapper::<Mmc1>().unwrap().chr_banks(), [0b10101, 0]);
+    }
+
+    #[test]
+    fn mmc1_state_round_trips() {
+        let mut mapper = Mmc1::new(numbered_banks(BankSize::Size16K, 8));
+        mapper.write(0x6000, 0x42, None);
+        for bit in [1, 0, 1] {
+            mapper.write(0xE000, bit, None);
+        }
+        let state = mapper.save_state();
+        let mut restored = Mmc1::new(numbered_banks(BankSize::Size16K, 8));
+        assert!(restored.load_state(&state));
+        assert_eq!(restored.save_state(), state);
+        // Finishing the register write lands in the same bank on both
+        for mapper in [&mut mapper, &mut restored] {
+            mapper.write(0xE000, 0, None);
+            mapper.write(0xE000, 0, None);
+            assert_eq!(mapper.read(0x8000, None), Some(5));
+            assert_eq!(mapper.read(0x6000, None), Some(0x42));
+        }
+        // A truncated state is rejected without changing anything
+        let before = restored.save_state();
+        assert!(!restored.load_state(&state[..4]));
+        assert_eq!(restored.save_state(), before);
+    }
+
+    #[test]
+    fn mmc1_reset_and_prg_ram() {
+        let mut memory = Memory::new();
+        memory.attach_mapper(Mmc1::new(numbered_banks(BankSize::Size16K, 2)));
+        // A reset part way through a register write starts it again
+        memory.write_byte(0x8000, 1);
+        memory.write_byte(0x8000, 0x80);
+        mmc1_write(&mut memory, 0xE000, 1);
+        assert_eq!(memory.read_byte(0x8000), 1);
+        memory.write_byte(0x6000, 0x42);
+        assert_eq!(memory.read_byte(0x6000), 0x42);
+        // Disabling PRG RAM leaves the RAM underneath visible, but keeps its contents
+        mmc1_write(&mut memory, 0xE000, 0b10001);
+        assert_eq!(memory.read_byte(0x6000), 0);
+        mmc1_write(&mut memory, 0xE000, 0b00001);
+        assert_eq!(memory.read_byte(0x6000), 0x42);
+    }
+
+    #[test]
+    fn c64_pla_follows_io_port() {
+        let mut memory = Memory::new();
+        memory.attach_io_port(IoPort::c64());
+        memory.attach_mapper(C64Pla::new(
+            vec![0xBA; 0x2000],
+            vec![0xCE; 0x2000],
+            vec![0xC6; 0x1000],
+        ));
+        memory.write_byte(0xA000, 0x11);
+        memory.write_byte(0xD000, 0x22);
+        memory.write_byte(0xE000, 0x33);
+        // Everything is banked in by the pull-ups after a reset, with I/O at $D000
+        assert_eq!(memory.read_byte(0xA000), 0xBA);
+        assert_eq!(memory.read_byte(0xD000), 0x22);
+        assert_eq!(memory.read_byte(0xE000), 0xCE);
+        // CHAREN low shows the character ROM
+        memory.write_byte(DIRECTION_REGISTER, 0b111);
+        memory.write_byte(DATA_REGISTER, LORAM | HIRAM);
+        assert_eq!(memory.read_byte(0xD000), 0xC6);
+        // HIRAM alone drops BASIC
+        memory.write_byte(DATA_REGISTER, HIRAM | CHAREN);
+        assert_eq!(memory.read_byte(0xA000), 0x11);
+        assert_eq!(memory.read_byte(0xE000), 0xCE);
+        // All RAM
+        memory.write_byte(DATA_REGISTER, 0);
+        assert_eq!(memory.read_byte(0xD000), 0x22);
+        assert_eq!(memory.read_byte(0xE000), 0x33);
+    }
+}
@@ -0,0 +1,35 @@
+use crate::variant::Variant;
+
+/// Everything the processor is connected to. Every cycle makes exactly one call to `read` or
+/// `write`, so implementations can model memory-mapped devices with side effects
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, byte: u8);
+    /// Reads without side effects, for debuggers, error reports and stepping back through
+    /// history. Returns `None` if that isn't possible for `addr`
+    fn peek(&self, _addr: u16) -> Option<u8> {
+        None
+    }
+    /// Whether writes to `addr` are ignored because it's ROM. Used by the processor's strict
+    /// mode to report stray writes
+    fn is_read_only(&self, _addr: u16) -> bool {
+        false
+    }
+    /// Captures everything a write to `addr` could change, such as RAM and device registers, so
+    /// that stepping back can put it back with `restore_write` without any side effects.
+    /// Returns `None` if that isn't possible for `addr`
+    fn save_write(&self, _addr: u16) -> Option<Vec<u8>> {
+        None
+    }
+    /// Puts back what `save_write` captured for `addr`
+    fn restore_write(&mut self, _addr: u16, _state: &[u8]) {}
+    /// The value open-bus reads return, if the bus keeps one. Put back with `drive`
+    fn open_bus_value(&self) -> Option<u8> {
+        None
+    }
+    /// Called with the value on the data bus when an access was handled by something in front
+    /// of this bus, such as a device, so that open-bus reads can return it
+    fn drive(&mut self, _value: u8) {}
+    /// Called when a processor emulating `variant` is powered on with this bus
+    fn connect(&mut self, _variant: Variant) {}
+}
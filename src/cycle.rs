@@ -0,0 +1,70 @@
+/// Direction of a bus access
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusAccess {
+    Read,
+    Write,
+}
+
+/// The bus access made during a single cycle. Every cycle of the 6502 performs exactly one,
+/// including the dummy reads and writes made while the CPU is busy internally
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusCycle {
+    pub address: u16,
+    pub data: u8,
+    pub access: BusAccess,
+}
+
+/// How an access should be performed while re-running a partially stepped instruction
+#[derive(Debug, PartialEq)]
+pub(crate) enum ReplayedAccess {
+    /// Already performed on an earlier cycle, so the recorded result is reused
+    Replayed(BusCycle),
+    /// This cycle's access, which goes out onto the bus
+    Live,
+    /// Belongs to a later cycle, so it must not happen yet
+    Suppressed,
+}
+
+/// Lets an instruction be run one cycle at a time without splitting the executor into states.
+/// The instruction is re-run from its start on every cycle: accesses from earlier cycles are
+/// replayed from the log, the next one is performed for real, and any after that are suppressed
+#[derive(Debug, Default)]
+pub(crate) struct Replay {
+    pub completed: Vec<BusCycle>,
+    position: usize,
+    pub live: Option<BusCycle>,
+    /// Set if the instruction tried to access the bus after this cycle's access
+    pub overran: bool,
+}
+
+impl Replay {
+    pub fn new(completed: Vec<BusCycle>) -> Self {
+        Replay {
+            completed,
+            ..Default::default()
+        }
+    }
+    /// Decides how the next access of the instruction should be performed
+    pub fn next_access(&mut self, address: u16, access: BusAccess) -> ReplayedAccess {
+        let position = self.position;
+        self.position += 1;
+        if let Some(&cycle) = self.completed.get(position) {
+            debug_assert_eq!(
+                (cycle.address, cycle.access),
+                (address, access),
+                "replayed instruction diverged from its earlier cycles"
+            );
+            ReplayedAccess::Replayed(cycle)
+        } else if self.live.is_none() {
+            ReplayedAccess::Live
+        } else {
+            self.overran = true;
+            ReplayedAccess::Suppressed
+        }
+    }
+    /// Records the access performed on this cycle
+    pub fn record(&mut self, cycle: BusCycle) {
+        self.completed.push(cycle);
+        self.live = Some(cycle);
+    }
+}
@@ -0,0 +1,263 @@
+// Decimal mode arithmetic, following Appendix A of http://www.6502.org/tutorials/decimal_mode.html
+
+/// Accumulator value and flags produced by a decimal mode ADC or SBC
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DecimalResult {
+    pub value: u8,
+    pub carry: bool,
+    pub zero: bool,
+    pub negative: bool,
+    pub overflow: bool,
+}
+
+/// ADC with the decimal flag set, as done by the NMOS 6502.
+/// Z is taken from the binary sum, while N and V come from the sum before the high digit is adjusted
+pub fn add(a: u8, b: u8, carry: bool) -> DecimalResult {
+    let carry = carry as i16;
+    let mut low = (a & 0x0F) as i16 + (b & 0x0F) as i16 + carry;
+    if low >= 0x0A {
+        low = ((low + 0x06) & 0x0F) + 0x10;
+    }
+    let mut sum = (a & 0xF0) as i16 + (b & 0xF0) as i16 + low;
+    // Same sum, but with the high digits treated as signed
+    let signed_sum = (a & 0xF0) as i8 as i16 + (b & 0xF0) as i8 as i16 + low;
+    let negative = (sum & 0x80) != 0;
+    let overflow = !(-128..=127).contains(&signed_sum);
+    if sum >= 0xA0 {
+        sum += 0x60;
+    }
+    DecimalResult {
+        value: sum as u8,
+        carry: sum >= 0x100,
+        zero: a.wrapping_add(b).wrapping_add(carry as u8) == 0,
+        negative,
+        overflow,
+    }
+}
+
+/// SBC with the decimal flag set, as done by the NMOS 6502.
+/// Only the accumulator is adjusted, all flags are the same as for a binary subtraction
+pub fn subtract(a: u8, b: u8, carry: bool) -> DecimalResult {
+    let borrow = !carry as i16;
+    let mut low = (a & 0x0F) as i16 - (b & 0x0F) as i16 - borrow;
+    if low < 0 {
+        low = ((low - 0x06) & 0x0F) - 0x10;
+    }
+    let mut difference = (a & 0xF0) as i16 - (b & 0xF0) as i16 + low;
+    if difference < 0 {
+        difference -= 0x60;
+    }
+    let binary = a as i16 - b as i16 - borrow;
+    let binary_value = binary as u8;
+    DecimalResult {
+        value: difference as u8,
+        carry: binary >= 0,
+        zero: binary_value == 0,
+        negative: (binary_value & 0b10000000) != 0,
+        overflow: ((a ^ b) & (a ^ binary_value) & 0b10000000) != 0,
+    }
+}
+
+/// ADC with the decimal flag set, as done by the 65C02.
+/// Gives the same result as the NMOS 6502, but N and Z are valid
+pub fn cmos_add(a: u8, b: u8, carry: bool) -> DecimalResult {
+    let result = add(a, b, carry);
+    DecimalResult {
+        zero: result.value == 0,
+        negative: (result.value & 0b10000000) != 0,
+        ..result
+    }
+}
+
+/// SBC with the decimal flag set, as done by the 65C02.
+/// C and V are the same as for a binary subtraction, while N and Z are valid.
+/// The accumulator only differs from the NMOS 6502 when an operand isn't valid BCD
+pub fn cmos_subtract(a: u8, b: u8, carry: bool) -> DecimalResult {
+    let borrow = !carry as i16;
+    let low = (a & 0x0F) as i16 - (b & 0x0F) as i16 - borrow;
+    let binary = a as i16 - b as i16 - borrow;
+    let mut difference = binary;
+    if difference < 0 {
+        difference -= 0x60;
+    }
+    if low < 0 {
+        difference -= 0x06;
+    }
+    let value = difference as u8;
+    let binary_value = binary as u8;
+    DecimalResult {
+        value,
+        carry: binary >= 0,
+        zero: value == 0,
+        negative: (value & 0b10000000) != 0,
+        overflow: ((a ^ b) & (a ^ binary_value) & 0b10000000) != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Digit-by-digit model of ADC, written independently of `add`
+    fn reference_add(a: u8, b: u8, carry: bool) -> DecimalResult {
+        let mut low = (a & 0x0F) as u16 + (b & 0x0F) as u16 + carry as u16;
+        let mut high = (a >> 4) as u16 + (b >> 4) as u16;
+        if low > 9 {
+            low += 6;
+        }
+        if low > 0x0F {
+            high += 1;
+        }
+        let negative = (high & 0x08) != 0;
+        let overflow = (((high << 4) ^ a as u16) & 0x80) != 0 && ((a ^ b) & 0x80) == 0;
+        if high > 9 {
+            high += 6;
+        }
+        DecimalResult {
+            value: ((high << 4) | (low & 0x0F)) as u8,
+            carry: high > 0x0F,
+            zero: (a as u16 + b as u16 + carry as u16) & 0xFF == 0,
+            negative,
+            overflow,
+        }
+    }
+
+    // Digit-by-digit model of SBC, written independently of `subtract`
+    fn reference_subtract(a: u8, b: u8, carry: bool) -> DecimalResult {
+        let borrow = !carry as i16;
+        let mut low = (a & 0x0F) as i16 - (b & 0x0F) as i16 - borrow;
+        let mut high = (a >> 4) as i16 - (b >> 4) as i16;
+        if (low & 0x10) != 0 {
+            low -= 6;
+            high -= 1;
+        }
+        if (high & 0x10) != 0 {
+            high -= 6;
+        }
+        let binary = a.wrapping_sub(b).wrapping_sub(borrow as u8);
+        DecimalResult {
+            value: ((high << 4) | (low & 0x0F)) as u8,
+            carry: a as i16 - b as i16 - borrow >= 0,
+            zero: binary == 0,
+            negative: (binary & 0x80) != 0,
+            overflow: ((a ^ b) & (a ^ binary) & 0x80) != 0,
+        }
+    }
+
+    fn to_bcd(value: u8) -> u8 {
+        ((value / 10) << 4) | (value % 10)
+    }
+
+    #[test]
+    fn add_matches_reference_for_all_operands() {
+        for a in 0..=255 {
+            for b in 0..=255 {
+                for carry in [false, true] {
+                    assert_eq!(
+                        add(a, b, carry),
+                        reference_add(a, b, carry),
+                        "${:02X} + ${:02X} + {}",
+                        a,
+                        b,
+                        carry as u8
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn subtract_matches_reference_for_all_operands() {
+        for a in 0..=255 {
+            for b in 0..=255 {
+                for carry in [false, true] {
+                    assert_eq!(
+                        subtract(a, b, carry),
+                        reference_subtract(a, b, carry),
+                        "${:02X} - ${:02X} - {}",
+                        a,
+                        b,
+                        !carry as u8
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn valid_bcd_arithmetic() {
+        for a in 0..100 {
+            for b in 0..100 {
+                for carry in [false, true] {
+                    let sum = a + b + carry as u8;
+                    let result = add(to_bcd(a), to_bcd(b), carry);
+                    assert_eq!(result.value, to_bcd(sum % 100));
+                    assert_eq!(result.carry, sum >= 100);
+
+                    let difference = a as i16 - b as i16 - !carry as i16;
+                    let result = subtract(to_bcd(a), to_bcd(b), carry);
+                    assert_eq!(result.value, to_bcd(difference.rem_euclid(100) as u8));
+                    assert_eq!(result.carry, difference >= 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn nmos_zero_flag_comes_from_binary_sum() {
+        // $99 + $01 is $00 in decimal, but $9A in binary
+        let result = add(0x99, 0x01, false);
+        assert_eq!(result.value, 0x00);
+        assert!(result.carry);
+        assert!(!result.zero);
+        assert!(result.negative);
+    }
+
+    #[test]
+    fn cmos_valid_bcd_arithmetic() {
+        for a in 0..100 {
+            for b in 0..100 {
+                for carry in [false, true] {
+                    let sum = (a + b + carry as u8) % 100;
+                    let result = cmos_add(to_bcd(a), to_bcd(b), carry);
+                    assert_eq!(result.value, to_bcd(sum));
+                    assert_eq!(result.zero, sum == 0);
+                    assert_eq!(result.negative, sum >= 80);
+
+                    let difference = (a as i16 - b as i16 - !carry as i16).rem_euclid(100) as u8;
+                    let result = cmos_subtract(to_bcd(a), to_bcd(b), carry);
+                    assert_eq!(result.value, to_bcd(difference));
+                    assert_eq!(result.zero, difference == 0);
+                    assert_eq!(result.negative, difference >= 80);
+                    assert_eq!(result, {
+                        let nmos = subtract(to_bcd(a), to_bcd(b), carry);
+                        DecimalResult {
+                            zero: result.zero,
+                            negative: result.negative,
+                            ..nmos
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cmos_add_only_differs_in_n_and_z() {
+        for a in 0..=255 {
+            for b in 0..=255 {
+                for carry in [false, true] {
+                    let nmos = add(a, b, carry);
+                    let cmos = cmos_add(a, b, carry);
+                    assert_eq!(
+                        (cmos.value, cmos.carry, cmos.overflow),
+                        (nmos.value, nmos.carry, nmos.overflow)
+                    );
+                }
+            }
+        }
+        let result = cmos_add(0x99, 0x01, false);
+        assert!(result.zero);
+        assert!(!result.negative);
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    any::Any,
+    fmt::{self, Debug},
+    ops::RangeInclusive,
+};
+
+use crate::{bus::Bus, error::MapError, memory::Memory, variant::Variant};
+
+/// Hardware mapped into the address space, like a UART, timer or GPIO port.
+/// Addresses are given as offsets from the start of the range the device is mapped to
+pub trait Device: Any {
+    fn read(&mut self, offset: u16) -> u8;
+    fn write(&mut self, offset: u16, byte: u8);
+    /// Reads without side effects, like [`Bus::peek`]
+    fn peek(&self, _offset: u16) -> Option<u8> {
+        None
+    }
+}
+
+/// Identifies a device mapped into a [`MappedBus`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceId(usize);
+
+struct Mapping {
+    id: DeviceId,
+    start: u16,
+    end: u16,
+    priority: i32,
+    device: Box<dyn Device>,
+}
+
+impl Debug for Mapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at ${:04X}-${:04X} (priority {})",
+            self.id, self.start, self.end, self.priority
+        )
+    }
+}
+
+/// A bus with devices mapped over parts of its address space. Accesses that don't hit a device
+/// go to the underlying bus, which is usually plain [`Memory`]
+#[derive(Debug)]
+pub struct MappedBus<B: Bus = Memory> {
+    bus: B,
+    // Sorted by priority, highest first, so the first match for an address wins
+    mappings: Vec<Mapping>,
+    next_id: usize,
+}
+
+impl<B: Bus> MappedBus<B> {
+    pub fn new(bus: B) -> Self {
+        MappedBus {
+            bus,
+            mappings: Vec::new(),
+            next_id: 0,
+        }
+    }
+    /// The bus underneath the devices
+    pub fn bus(&self) -> &B {
+        &self.bus
+    }
+    pub fn bus_mut(&mut self) -> &mut B {
+        &mut self.bus
+    }
+    /// Maps `device` over `range`. Where devices overlap, the one with the higher `priority`
+    /// handles the access. Overlapping a device with the same priority is an error, as it
+    /// wouldn't be clear which one should win
+    pub fn map(
+        &mut self,
+        range: RangeInclusive<u16>,
+        priority: i32,
+        device: impl Device,
+    ) -> Result<DeviceId, MapError> {
+        let (start, end) = range.into_inner();
+        if start > end {
+            return Err(MapError::EmptyRange);
+        }
+        if let Some(existing) = self.mappings.iter().find(|mapping| {
+            mapping.priority == priority && mapping.start <= end && start <= mapping.end
+        }) {
+            return Err(MapError::Overlap {
+                existing: existing.id,
+                start: existing.start,
+                end: existing.end,
+            });
+        }
+        let id = DeviceId(self.next_id);
+        self.next_id += 1;
+        let index = self
+            .mappings
+            .partition_point(|mapping| mapping.priority >= priority);
+        self.mappings.insert(
+            index,
+            Mapping {
+                id,
+                start,
+                end,
+                priority,
+                device: Box::new(device),
+            },
+        );
+        Ok(id)
+    }
+    /// Removes a device, uncovering whatever was underneath it
+    pub fn unmap(&mut self, id: DeviceId) -> Option<Box<dyn Device>> {
+        let index = self.mappings.iter().position(|mapping| mapping.id == id)?;
+        Some(self.mappings.remove(index).device)
+    }
+    /// The device with the given id, if it's still mapped and is a `T`
+    pub fn device<T: Device>(&self, id: DeviceId) -> Option<&T> {
+        let mapping = self.mappings.iter().find(|mapping| mapping.id == id)?;
+        (mapping.device.as_ref() as &dyn Any).downcast_ref()
+    }
+    pub fn device_mut<T: Device>(&mut self, id: DeviceId) -> Option<&mut T> {
+        let mapping = self.mappings.iter_mut().find(|mapping| mapping.id == id)?;
+        (mapping.device.as_mut() as &mut dyn Any).downcast_mut()
+    }
+    fn mapping_at(&self, addr: u16) -> Option<usize> {
+        self.mappings
+            .iter()
+            .position(|mapping| (mapping.start..=mapping.end).contains(&addr))
+    }
+}
+
+impl<B: Bus> Bus for MappedBus<B> {
+    fn read(&mut self, addr: u16) -> u8 {
+        match self.mapping_at(addr) {
+            Some(index) => {
+                let mapping = &mut self.mappings[index];
+                let value = mapping.device.read(addr - mapping.start);
+                self.bus.drive(value);
+                value
+            }
+            None => self.bus.read(addr),
+        }
+    }
+    fn write(&mut self, addr: u16, byte: u8) {
+        match self.mapping_at(addr) {
+            Some(index) => {
+                let mapping = &mut self.mappings[index];
+                mapping.device.write(addr - mapping.start, byte);
+                self.bus.drive(byte);
+            }
+            None => self.bus.write(addr, byte),
+        }
+    }
+    fn peek(&self, addr: u16) -> Option<u8> {
+        match self.mapping_at(addr) {
+            Some(index) => {
+                let mapping = &self.mappings[index];
+                mapping.device.peek(addr - mapping.start)
+            }
+            None => self.bus.peek(addr),
+        }
+    }
+    /// Devices take writes, so only addresses falling through to the bus can be read-only
+    fn is_read_only(&self, addr: u16) -> bool {
+        self.mapping_at(addr).is_none() && self.bus.is_read_only(addr)
+    }
+    /// Device registers can't be captured, so only writes falling through to the bus are
+    fn save_write(&self, addr: u16) -> Option<Vec<u8>> {
+        match self.mapping_at(addr) {
+            Some(_) => None,
+            None => self.bus.save_write(addr),
+        }
+    }
+    fn restore_write(&mut self, addr: u16, state: &[u8]) {
+        self.bus.restore_write(addr, state);
+    }
+    fn open_bus_value(&self) -> Option<u8> {
+        self.bus.open_bus_value()
+    }
+    fn drive(&mut self, value: u8) {
+        self.bus.drive(value);
+    }
+    fn connect(&mut self, variant: Variant) {
+        self.bus.connect(variant);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::{Processor, StopReason};
+
+    /// Collects every byte written to its data register at offset 0
+    #[derive(Default)]
+    struct Uart {
+        sent: Vec<u8>,
+    }
+
+    impl Device for Uart {
+        fn read(&mut self, offset: u16) -> u8 {
+            // The status register always says it's ready to send
+            if offset == 1 { 0x80 } else { 0 }
+        }
+        fn write(&mut self, offset: u16, byte: u8) {
+            if offset == 0 {
+                self.sent.push(byte);
+            }
+        }
+    }
+
+    /// Counts up every time it's read
+    #[derive(Default)]
+    struct Timer {
+        count: u8,
+    }
+
+    impl Device for Timer {
+        fn read(&mut self, _offset: u16) -> u8 {
+            self.count = self.count.wrapping_add(1);
+            self.count
+        }
+        fn write(&mut self, _offset: u16, byte: u8) {
+            self.count = byte;
+        }
+        fn peek(&self, _offset: u16) -> Option<u8> {
+            Some(self.count)
+        }
+    }
+
+    #[test]
+    fn program_talks_to_devices() {
+        let mut memory = Memory::new();
+        // LDX #$00; loop: LDA $1100,X; BEQ done; STA $D000; INX; BNE loop; done: BRK
+        memory.write_bytes(
+            0x1000,
+            &[
+                0xA2, 0x00, 0xBD, 0x00, 0x11, 0xF0, 0x06, 0x8D, 0x00, 0xD0, 0xE8, 0xD0, 0xF5,
+            ],
+        );
+        memory.write_bytes(0x1100, b"hi\0");
+        let mut bus = MappedBus::new(memory);
+        let uart = bus.map(0xD000..=0xD001, 0, Uart::default()).unwrap();
+        let mut processor = Processor::with_start_address(bus, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        let uart = processor.memory.device::<Uart>(uart).unwrap();
+        assert_eq!(uart.sent, b"hi");
+        assert_eq!(processor.memory.peek(0xD001), None);
+    }
+
+    #[test]
+    fn devices_get_offsets_and_fall_through() {
+        let mut bus = MappedBus::new(Memory::new());
+        let timer = bus.map(0xDC04..=0xDC05, 0, Timer::default()).unwrap();
+        bus.bus_mut().write_byte(0xDC06, 0x42);
+        assert_eq!(bus.read(0xDC04), 1);
+        assert_eq!(bus.read(0xDC05), 2);
+        assert_eq!(bus.peek(0xDC05), Some(2));
+        assert_eq!(bus.read(0xDC06), 0x42);
+        bus.write(0xDC04, 0x10);
+        assert_eq!(bus.device::<Timer>(timer).unwrap().count, 0x10);
+        assert!(bus.device::<Uart>(timer).is_none());
+        // Writes don't reach the memory underneath
+        assert_eq!(bus.bus().read_byte(0xDC04), 0);
+    }
+
+    #[test]
+    fn devices_drive_open_bus() {
+        let mut memory = Memory::new();
+        memory.mark_unmapped(0xD100..=0xD1FF);
+        let mut bus = MappedBus::new(memory);
+        bus.map(0xD000..=0xD001, 0, Uart::default()).unwrap();
+        assert_eq!(bus.read(0xD001), 0x80);
+        assert_eq!(bus.read(0xD100), 0x80);
+        bus.write(0xD000, 0x41);
+        assert_eq!(bus.read(0xD1FF), 0x41);
+    }
+
+    #[test]
+    fn higher_priority_wins_overlaps() {
+        let mut bus = MappedBus::new(Memory::new());
+        let low = bus.map(0xD000..=0xD0FF, 0, Timer::default()).unwrap();
+        let high = bus.map(0xD080..=0xD081, 1, Uart::default()).unwrap();
+        assert_eq!(bus.read(0xD081), 0x80);
+        assert_eq!(bus.read(0xD082), 1);
+        assert!(bus.unmap(high).is_some());
+        assert_eq!(bus.read(0xD081), 2);
+        assert!(bus.unmap(high).is_none());
+        assert!(bus.device_mut::<Timer>(low).is_some());
+    }
+
+    #[test]
+    fn detects_overlaps_at_the_same_priority() {
+        let mut bus = MappedBus::new(Memory::new());
+        let timer = bus.map(0xD000..=0xD00F, 0, Timer::default()).unwrap();
+        assert_eq!(
+            bus.map(0xD00F..=0xD010, 0, Uart::default()),
+            Err(MapError::Overlap {
+                existing: timer,
+                start: 0xD000,
+                end: 0xD00F,
+            })
+        );
+        assert!(bus.map(0xD010..=0xD011, 0, Uart::default()).is_ok());
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 0xD020..=0xD01F;
+        assert_eq!(
+            bus.map(empty, 0, Uart::default()),
+            Err(MapError::EmptyRange)
+        );
+    }
+}
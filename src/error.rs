@@ -0,0 +1,201 @@
+use std::{
+    fmt::{self, Display},
+    io,
+};
+
+use crate::{addressing::AddressingMode, devices::DeviceId};
+
+/// Returned when an opcode can't be decoded into an `Instruction`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The opcode isn't one of the 151 documented by MOS
+    Undocumented(u8),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Undocumented(opcode) => {
+                write!(f, "${:02X} is not a documented opcode", opcode)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Returned by `Processor::process_next_instruction` when an instruction can't be run.
+/// `bytes` holds the opcode and its operands, as read from memory at `pc`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CpuError {
+    /// The opcode has no defined behaviour on this variant
+    UnknownOpcode { pc: u16, bytes: Vec<u8> },
+    /// The instruction uses an addressing mode this variant doesn't have
+    UnimplementedMode {
+        pc: u16,
+        bytes: Vec<u8>,
+        addressing_mode: AddressingMode,
+    },
+    /// A JAM (or STP on the 65C02) has locked up the processor until it's reset
+    Jammed { pc: u16, bytes: Vec<u8> },
+    /// The instruction (or the interrupt taken before it) wrote `value` to ROM at `address`,
+    /// with the processor in strict mode
+    RomWrite {
+        pc: u16,
+        bytes: Vec<u8>,
+        address: u16,
+        value: u8,
+    },
+}
+
+impl CpuError {
+    /// Address of the instruction that caused the error
+    pub fn pc(&self) -> u16 {
+        match self {
+            CpuError::UnknownOpcode { pc, .. }
+            | CpuError::UnimplementedMode { pc, .. }
+            | CpuError::Jammed { pc, .. }
+            | CpuError::RomWrite { pc, .. } => *pc,
+        }
+    }
+    /// The instruction's opcode and operands
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            CpuError::UnknownOpcode { bytes, .. }
+            | CpuError::UnimplementedMode { bytes, .. }
+            | CpuError::Jammed { bytes, .. }
+            | CpuError::RomWrite { bytes, .. } => bytes,
+        }
+    }
+}
+
+impl Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { .. } => write!(f, "unknown opcode")?,
+            CpuError::UnimplementedMode {
+                addressing_mode, ..
+            } => write!(f, "unimplemented addressing mode {:?}", addressing_mode)?,
+            CpuError::Jammed { .. } => write!(f, "processor jammed")?,
+            CpuError::RomWrite { address, value, .. } => {
+                write!(f, "wrote ${:02X} to ROM address ${:04X}", value, address)?
+            }
+        }
+        write!(f, " at ${:04X}:", self.pc())?;
+        for byte in self.bytes() {
+            write!(f, " {:02X}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+/// Returned when a save state can't be written or read
+#[derive(Debug)]
+pub enum SaveStateError {
+    Io(io::Error),
+    /// The data doesn't start with the save state magic number
+    NotASaveState,
+    /// The save state was written with a layout this version can't read
+    UnsupportedVersion(u16),
+    /// A field holds a value that can't have been saved
+    Corrupt(&'static str),
+    /// The state was saved with a mapper and is being loaded without one, or the other way
+    /// round, or the attached mapper couldn't restore its part of the state
+    MapperMismatch,
+}
+
+impl From<io::Error> for SaveStateError {
+    fn from(error: io::Error) -> Self {
+        SaveStateError::Io(error)
+    }
+}
+
+impl Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::Io(error) => write!(f, "{}", error),
+            SaveStateError::NotASaveState => write!(f, "not a save state"),
+            SaveStateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            SaveStateError::Corrupt(reason) => write!(f, "corrupt save state: {}", reason),
+            SaveStateError::MapperMismatch => write!(f, "save state doesn't match the mapper"),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SaveStateError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Returned when a device can't be mapped into a `MappedBus`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapError {
+    /// The range doesn't contain any addresses
+    EmptyRange,
+    /// The range overlaps a device with the same priority, mapped from `start` to `end`
+    Overlap {
+        existing: DeviceId,
+        start: u16,
+        end: u16,
+    },
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::EmptyRange => write!(f, "empty address range"),
+            MapError::Overlap {
+                existing,
+                start,
+                end,
+            } => write!(
+                f,
+                "overlaps {:?} at ${:04X}-${:04X} with the same priority",
+                existing, start, end
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_includes_pc_and_bytes() {
+        let error = CpuError::Jammed {
+            pc: 0x1000,
+            bytes: vec![0x02],
+        };
+        assert_eq!(error.to_string(), "processor jammed at $1000: 02");
+        let error = CpuError::UnimplementedMode {
+            pc: 0xC000,
+            bytes: vec![0x72, 0x10],
+            addressing_mode: AddressingMode::ZeroPageIndirect,
+        };
+        assert_eq!(
+            error.to_string(),
+            "unimplemented addressing mode ZeroPageIndirect at $C000: 72 10"
+        );
+        let error = CpuError::RomWrite {
+            pc: 0x1002,
+            bytes: vec![0x8D, 0x00, 0xC0],
+            address: 0xC000,
+            value: 0x42,
+        };
+        assert_eq!(
+            error.to_string(),
+            "wrote $42 to ROM address $C000 at $1002: 8D 00 C0"
+        );
+    }
+}
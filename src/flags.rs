@@ -1,4 +1,59 @@
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
+
+/// A bit of the status register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusFlag {
+    Carry,
+    Zero,
+    InterruptDisable,
+    Decimal,
+    /// Only exists in copies of the status register pushed onto the stack
+    Break,
+    /// Bit 5, which always reads as set
+    Unused,
+    Overflow,
+    Negative,
+}
+
+impl StatusFlag {
+    /// Every flag, from bit 7 down to bit 0
+    pub const ALL: [StatusFlag; 8] = [
+        StatusFlag::Negative,
+        StatusFlag::Overflow,
+        StatusFlag::Unused,
+        StatusFlag::Break,
+        StatusFlag::Decimal,
+        StatusFlag::InterruptDisable,
+        StatusFlag::Zero,
+        StatusFlag::Carry,
+    ];
+    /// The flag's bit in the status register
+    pub const fn mask(self) -> u8 {
+        match self {
+            StatusFlag::Carry => 0b00000001,
+            StatusFlag::Zero => 0b00000010,
+            StatusFlag::InterruptDisable => 0b00000100,
+            StatusFlag::Decimal => 0b00001000,
+            StatusFlag::Break => 0b00010000,
+            StatusFlag::Unused => 0b00100000,
+            StatusFlag::Overflow => 0b01000000,
+            StatusFlag::Negative => 0b10000000,
+        }
+    }
+    /// The flag's letter in `NV-BDIZC`
+    pub const fn letter(self) -> char {
+        match self {
+            StatusFlag::Carry => 'C',
+            StatusFlag::Zero => 'Z',
+            StatusFlag::InterruptDisable => 'I',
+            StatusFlag::Decimal => 'D',
+            StatusFlag::Break => 'B',
+            StatusFlag::Unused => '-',
+            StatusFlag::Overflow => 'V',
+            StatusFlag::Negative => 'N',
+        }
+    }
+}
 
 /// Wrapper around a `u8` with convenience methods for manually setting specific bits
 impl Debug for Flags {
@@ -7,6 +62,7 @@ impl Debug for Flags {
         Ok(())
     }
 }
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct Flags(u8);
 
 impl Default for Flags {
@@ -15,80 +71,72 @@ impl Default for Flags {
     }
 }
 
+impl From<u8> for Flags {
+    fn from(bits: u8) -> Self {
+        Self(bits)
+    }
+}
+
+impl Display for Flags {
+    /// Renders the flags as `NV-BDIZC`, with set flags in upper case and clear ones in lower case
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (flag, set) in self.iter() {
+            let letter = flag.letter();
+            if set {
+                write!(f, "{}", letter)?;
+            } else {
+                write!(f, "{}", letter.to_ascii_lowercase())?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Flags {
     pub fn new() -> Self {
         // This bit is initialised to 1 apparently: https://www.nesdev.org/wiki/Status_flags
         Self(0b00100000)
     }
-    // Carry Flag
-    pub fn get_carry_flag(&self) -> bool {
-        (self.0 & 0b00000001) != 0
-    }
-    pub fn set_carry_flag(&mut self) {
-        self.0 |= 0b00000001
-    }
-    pub fn clear_carry_flag(&mut self) {
-        self.0 &= 0b11111110
-    }
-    // Zero Flag
-    pub fn get_zero_flag(&self) -> bool {
-        (self.0 & 0b00000010) != 0
-    }
-    pub fn set_zero_flag(&mut self) {
-        self.0 |= 0b00000010
-    }
-    pub fn clear_zero_flag(&mut self) {
-        self.0 &= 0b11111101
-    }
-    // Interrupt Disable Flag
-    pub fn get_interrupt_disable_flag(&self) -> bool {
-        (self.0 & 0b00000100) != 0
-    }
-    pub fn set_interrupt_disable_flag(&mut self) {
-        self.0 |= 0b00000100
-    }
-    pub fn clear_interrupt_disable_flag(&mut self) {
-        self.0 &= 0b11111011
-    }
-    // Decimal Mode Flag
-    pub fn get_decimal_mode_flag(&self) -> bool {
-        (self.0 & 0b00001000) != 0
-    }
-    pub fn set_decimal_mode_flag(&mut self) {
-        self.0 |= 0b00001000
-    }
-    pub fn clear_decimal_mode_flag(&mut self) {
-        self.0 &= 0b11110111
-    }
-    // Break Command Flag
-    pub fn get_break_command_flag(&self) -> bool {
-        (self.0 & 0b00010000) != 0
-    }
-    pub fn set_break_command_flag(&mut self) {
-        self.0 |= 0b00010000
-    }
-    pub fn clear_break_command_flag(&mut self) {
-        self.0 &= 0b11101111
-    }
-    // Overflow flag
-    pub fn get_overflow_flag(&self) -> bool {
-        (self.0 & 0b01000000) != 0
-    }
-    pub fn set_overflow_flag(&mut self) {
-        self.0 |= 0b01000000
-    }
-    pub fn clear_overflow_flag(&mut self) {
-        self.0 &= 0b10111111
-    }
-    // Negative Flag
-    pub fn get_negative_flag(&self) -> bool {
-        (self.0 & 0b10000000) != 0
-    }
-    pub fn set_negative_flag(&mut self) {
-        self.0 |= 0b10000000
-    }
-    pub fn clear_negative_flag(&mut self) {
-        self.0 &= 0b01111111
+    pub fn get(&self, flag: StatusFlag) -> bool {
+        (self.0 & flag.mask()) != 0
+    }
+    pub fn set(&mut self, flag: StatusFlag) {
+        self.0 |= flag.mask();
+    }
+    pub fn clear(&mut self, flag: StatusFlag) {
+        self.0 &= !flag.mask();
+    }
+    pub fn toggle(&mut self, flag: StatusFlag) {
+        self.0 ^= flag.mask();
+    }
+    /// Sets `flag` if `value` is `true`, otherwise clears it
+    pub fn update(&mut self, flag: StatusFlag, value: bool) {
+        if value {
+            self.set(flag);
+        } else {
+            self.clear(flag);
+        }
+    }
+    /// Each flag with whether it's set, from bit 7 down to bit 0
+    pub fn iter(&self) -> impl Iterator<Item = (StatusFlag, bool)> + '_ {
+        StatusFlag::ALL
+            .into_iter()
+            .map(|flag| (flag, self.get(flag)))
+    }
+    /// The status as pushed onto the stack. Bit 5 is always set, and B is set by PHP and BRK
+    /// but clear for IRQ and NMI
+    pub fn to_pushed(&self, break_flag: bool) -> u8 {
+        let mut pushed = Flags(self.0);
+        pushed.set(StatusFlag::Unused);
+        pushed.update(StatusFlag::Break, break_flag);
+        pushed.0
+    }
+    /// Loads a status pulled from the stack by PLP or RTI. B doesn't exist in the register
+    /// itself, so it's dropped, and bit 5 is always set
+    pub fn set_pulled(&mut self, pulled: u8) {
+        self.0 = pulled;
+        self.clear(StatusFlag::Break);
+        self.set(StatusFlag::Unused);
     }
     pub fn raw(&self) -> &u8 {
         &self.0
@@ -105,32 +153,71 @@ mod tests {
     #[test]
     fn carry_flag() {
         let mut flags = Flags::new();
-        flags.set_carry_flag();
-        assert!(flags.get_carry_flag());
+        flags.set(StatusFlag::Carry);
+        assert!(flags.get(StatusFlag::Carry));
         assert_eq!(flags.0, 0b00100001);
-        flags.clear_carry_flag();
-        assert!(!flags.get_carry_flag());
+        flags.clear(StatusFlag::Carry);
+        assert!(!flags.get(StatusFlag::Carry));
         assert_eq!(flags.0, 0b00100000);
     }
 
     #[test]
     fn all_flags() {
         let mut flags = Flags::new();
-        flags.set_carry_flag();
-        flags.set_zero_flag();
-        flags.set_interrupt_disable_flag();
-        flags.set_decimal_mode_flag();
-        flags.set_break_command_flag();
-        flags.set_overflow_flag();
-        flags.set_negative_flag();
+        for flag in StatusFlag::ALL {
+            flags.set(flag);
+        }
         assert_eq!(flags.0, 255);
-        flags.clear_carry_flag();
-        flags.clear_zero_flag();
-        flags.clear_interrupt_disable_flag();
-        flags.clear_decimal_mode_flag();
-        flags.clear_break_command_flag();
-        flags.clear_overflow_flag();
-        flags.clear_negative_flag();
+        for flag in StatusFlag::ALL {
+            flags.clear(flag);
+        }
+        assert_eq!(flags.0, 0);
+        assert_eq!(
+            StatusFlag::ALL
+                .iter()
+                .fold(0, |bits, flag| bits | flag.mask()),
+            255
+        );
+    }
+
+    #[test]
+    fn toggle_and_update() {
+        let mut flags = Flags::new();
+        flags.toggle(StatusFlag::Zero);
+        assert!(flags.get(StatusFlag::Zero));
+        flags.toggle(StatusFlag::Zero);
+        assert!(!flags.get(StatusFlag::Zero));
+        flags.update(StatusFlag::Overflow, true);
+        assert_eq!(flags.0, 0b01100000);
+        flags.update(StatusFlag::Overflow, false);
         assert_eq!(flags.0, 0b00100000);
     }
+
+    #[test]
+    fn iterates_from_bit_7() {
+        let flags = Flags::from(0b10000001);
+        let set: Vec<_> = flags
+            .iter()
+            .filter(|&(_, set)| set)
+            .map(|(flag, _)| flag)
+            .collect();
+        assert_eq!(set, [StatusFlag::Negative, StatusFlag::Carry]);
+    }
+
+    #[test]
+    fn display_uses_case_for_state() {
+        assert_eq!(Flags::new().to_string(), "nv-bdizc");
+        assert_eq!(Flags::from(0b11001011).to_string(), "NV-bDiZC");
+        assert_eq!(Flags::from(0b00110100).to_string(), "nv-BdIzc");
+    }
+
+    #[test]
+    fn pushed_break_and_bit_5() {
+        let flags = Flags::from(0b00000001);
+        assert_eq!(flags.to_pushed(true), 0b00110001);
+        assert_eq!(flags.to_pushed(false), 0b00100001);
+        let mut flags = Flags::new();
+        flags.set_pulled(0b11010000);
+        assert_eq!(flags.0, 0b11100000);
+    }
 }
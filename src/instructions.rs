@@ -1,3 +1,9 @@
+use crate::{
+    error::DecodeError,
+    opcodes::{NMOS_OPCODES, Opcode},
+    variant::Variant,
+};
+
 // Instructions are grouped as shown on https://llx.com/Neil/a2/opcodes.html
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Group1Instruction {
@@ -28,7 +34,6 @@ pub enum Group3Instruction {
     // Group Three
     BIT,
     JMP,
-    // JMPABS,
     STY,
     LDY,
     CPY,
@@ -83,6 +88,64 @@ pub enum SpecialCase {
     // Apparently "only absolute-addressing instruction that doesn't fit the aaabbbcc"
     JSRABS,
 }
+// Undocumented NMOS opcodes, named as on https://www.masswerk.at/6502/6502_instruction_set.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IllegalInstruction {
+    // Read-modify-write combined with a group one instruction
+    SLO, // ASL + ORA
+    RLA, // ROL + AND
+    SRE, // LSR + EOR
+    RRA, // ROR + ADC
+    DCP, // DEC + CMP
+    ISC, // INC + SBC
+
+    SAX, // Store A & X
+    LAX, // Load A and X
+    LAS, // Load A, X and S with memory & S
+
+    // Immediate only
+    ANC,  // AND, then copy N to C
+    ALR,  // AND, then LSR A
+    ARR,  // AND, then ROR A with odd flags
+    SBX,  // X = (A & X) - operand
+    USBC, // Same as SBC
+
+    // Unstable, see `Processor::set_magic_constant`
+    XAA, // A = (A | magic) & X & operand
+    LXA, // A = X = (A | magic) & operand
+
+    // Store a register ANDed with the high byte of the address plus one
+    SHA,
+    SHX,
+    SHY,
+    TAS,
+
+    NOP,
+    // Locks up the processor until it is reset
+    JAM,
+}
+// Instructions added by the 65C02. The documented NMOS instructions gain some extra addressing
+// modes there too, which are decoded as the usual group one to three instructions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CmosInstruction {
+    BRA, // Branch always
+    PHX,
+    PHY,
+    PLX,
+    PLY,
+    STZ, // Store zero
+    TRB, // Test and reset bits
+    TSB, // Test and set bits
+    // Rockwell bit instructions, on the given bit of a zero page byte
+    RMB(u8), // Reset memory bit
+    SMB(u8), // Set memory bit
+    BBR(u8), // Branch on bit reset
+    BBS(u8), // Branch on bit set
+    WAI,     // Wait for interrupt
+    STP,     // Stop until reset
+    // Every undefined opcode is a NOP, though they differ in length and timing
+    NOP,
+}
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Instruction {
     GroupOne(Group1Instruction),
@@ -91,116 +154,86 @@ pub enum Instruction {
     ConditionalBranch(ConditionalBranchInstruction),
     SingleByte(SingleByteInstruction),
     SpecialCase(SpecialCase),
+    Illegal(IllegalInstruction),
+    Cmos(CmosInstruction),
 }
-// Parse instructions that don't follow aaabbbcc rule
-fn parse_conditional_branch_instruction(opcode: u8) -> Option<ConditionalBranchInstruction> {
-    if opcode & 0b11111 == 0b10000 {
-        return Some(match opcode {
-            0x10 => ConditionalBranchInstruction::BPL,
-            0x30 => ConditionalBranchInstruction::BMI,
-            0x50 => ConditionalBranchInstruction::BVC,
-            0x70 => ConditionalBranchInstruction::BVS,
-            0x90 => ConditionalBranchInstruction::BCC,
-            0xB0 => ConditionalBranchInstruction::BCS,
-            0xD0 => ConditionalBranchInstruction::BNE,
-            0xF0 => ConditionalBranchInstruction::BEQ,
-            _ => panic!("Unsupported!"),
-        });
-    }
-    None
+/// How an instruction uses the memory at its effective address
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperandAccess {
+    Read,
+    Write,
+    ReadModifyWrite,
+    /// The effective address isn't accessed, e.g. it's a jump target
+    None,
 }
-fn parse_special_case(opcode: u8) -> Option<SpecialCase> {
-    if opcode == 0x20 {
-        Some(SpecialCase::JSRABS)
-    } else {
-        None
+impl Instruction {
+    pub fn operand_access(&self) -> OperandAccess {
+        match self {
+            Instruction::GroupThree(Group3Instruction::JMP) => OperandAccess::None,
+            Instruction::GroupOne(Group1Instruction::STA)
+            | Instruction::GroupTwo(Group2Instruction::STX)
+            | Instruction::GroupThree(Group3Instruction::STY) => OperandAccess::Write,
+            Instruction::GroupOne(_)
+            | Instruction::GroupTwo(Group2Instruction::LDX)
+            | Instruction::GroupThree(_) => OperandAccess::Read,
+            Instruction::GroupTwo(_) => OperandAccess::ReadModifyWrite,
+            Instruction::ConditionalBranch(_)
+            | Instruction::SingleByte(_)
+            | Instruction::SpecialCase(_) => OperandAccess::None,
+            Instruction::Illegal(instruction) => match instruction {
+                IllegalInstruction::SLO
+                | IllegalInstruction::RLA
+                | IllegalInstruction::SRE
+                | IllegalInstruction::RRA
+                | IllegalInstruction::DCP
+                | IllegalInstruction::ISC => OperandAccess::ReadModifyWrite,
+                IllegalInstruction::SAX
+                | IllegalInstruction::SHA
+                | IllegalInstruction::SHX
+                | IllegalInstruction::SHY
+                | IllegalInstruction::TAS => OperandAccess::Write,
+                IllegalInstruction::JAM => OperandAccess::None,
+                _ => OperandAccess::Read,
+            },
+            Instruction::Cmos(instruction) => match instruction {
+                CmosInstruction::STZ => OperandAccess::Write,
+                CmosInstruction::TRB
+                | CmosInstruction::TSB
+                | CmosInstruction::RMB(_)
+                | CmosInstruction::SMB(_) => OperandAccess::ReadModifyWrite,
+                CmosInstruction::BBR(_) | CmosInstruction::BBS(_) | CmosInstruction::NOP => {
+                    OperandAccess::Read
+                }
+                _ => OperandAccess::None,
+            },
+        }
     }
-}
-fn parse_other_single_byte_instruction(opcode: u8) -> Option<SingleByteInstruction> {
-    match opcode {
-        0x00 => Some(SingleByteInstruction::BRK),
-        0x40 => Some(SingleByteInstruction::RTI),
-        0x60 => Some(SingleByteInstruction::RTS),
-
-        0x08 => Some(SingleByteInstruction::PHP),
-        0x28 => Some(SingleByteInstruction::PLP),
-        0x48 => Some(SingleByteInstruction::PHA),
-        0x68 => Some(SingleByteInstruction::PLA),
-        0x88 => Some(SingleByteInstruction::DEY),
-        0xA8 => Some(SingleByteInstruction::TAY),
-        0xC8 => Some(SingleByteInstruction::INY),
-        0xE8 => Some(SingleByteInstruction::INX),
-
-        0x18 => Some(SingleByteInstruction::CLC),
-        0x38 => Some(SingleByteInstruction::SEC),
-        0x58 => Some(SingleByteInstruction::CLI),
-        0x78 => Some(SingleByteInstruction::SEI),
-        0x98 => Some(SingleByteInstruction::TYA),
-        0xB8 => Some(SingleByteInstruction::CLV),
-        0xD8 => Some(SingleByteInstruction::CLD),
-        0xF8 => Some(SingleByteInstruction::SED),
-
-        0x8A => Some(SingleByteInstruction::TXA),
-        0x9A => Some(SingleByteInstruction::TXS),
-        0xAA => Some(SingleByteInstruction::TAX),
-        0xBA => Some(SingleByteInstruction::TSX),
-        0xCA => Some(SingleByteInstruction::DEX),
-        0xEA => Some(SingleByteInstruction::NOP),
-        _ => None,
+    /// Decodes `opcode` as it would be run by the given `variant`
+    pub fn decode(opcode: u8, variant: Variant) -> Instruction {
+        Opcode::decode(opcode, variant).instruction
     }
-}
-fn parse_single_byte_instruction(opcode: u8) -> Option<Instruction> {
-    if let Some(conditional_branch) = parse_conditional_branch_instruction(opcode) {
-        return Some(Instruction::ConditionalBranch(conditional_branch));
+    /// Whether this is one of the 151 opcodes documented by MOS
+    pub fn is_documented(&self) -> bool {
+        !matches!(self, Instruction::Illegal(_) | Instruction::Cmos(_))
     }
-    if let Some(special_case) = parse_special_case(opcode) {
-        return Some(Instruction::SpecialCase(special_case));
+    /// Whether the opcode has a defined behaviour. That's the documented opcodes and the
+    /// 65C02's additions, but not the NMOS undocumented opcodes or the 65C02's filler NOPs
+    pub fn is_defined(&self) -> bool {
+        !matches!(
+            self,
+            Instruction::Illegal(_) | Instruction::Cmos(CmosInstruction::NOP)
+        )
     }
-    // Check for other single byte instructions
-    parse_other_single_byte_instruction(opcode).map(Instruction::SingleByte)
 }
-impl From<u8> for Instruction {
-    fn from(opcode: u8) -> Self {
-        if let Some(instruction) = parse_single_byte_instruction(opcode) {
-            return instruction;
-        }
-        let cc = opcode & 0b11;
-        let aaa = (opcode & 0b11100000) >> 5;
-        match cc {
-            // Group One
-            0b01 => Instruction::GroupOne(match aaa {
-                0b000 => Group1Instruction::ORA,
-                0b001 => Group1Instruction::AND,
-                0b010 => Group1Instruction::EOR,
-                0b011 => Group1Instruction::ADC,
-                0b100 => Group1Instruction::STA,
-                0b101 => Group1Instruction::LDA,
-                0b110 => Group1Instruction::CMP,
-                0b111 => Group1Instruction::SBC,
-                _ => panic!("Unsupported instruction"),
-            }),
-            // Group Two
-            0b10 => Instruction::GroupTwo(match aaa {
-                0b000 => Group2Instruction::ASL,
-                0b001 => Group2Instruction::LSR,
-                0b010 => Group2Instruction::ROR,
-                0b100 => Group2Instruction::STX,
-                0b101 => Group2Instruction::LDX,
-                0b110 => Group2Instruction::DEC,
-                0b111 => Group2Instruction::INC,
-                _ => panic!("Unsupported"),
-            }),
-            0b00 => Instruction::GroupThree(match aaa {
-                0b001 => Group3Instruction::BIT,
-                0b010 => Group3Instruction::JMP,
-                // 0b011 => Group3Instruction::JMPABS,
-                0b100 => Group3Instruction::STY,
-                0b101 => Group3Instruction::LDY,
-                0b110 => Group3Instruction::CPY,
-                0b111 => Group3Instruction::CPX,
-                _ => panic!("Unsupported"),
-            }),
-            _ => panic!("Unsupported instruction, opcode ${:x}", opcode),
+impl TryFrom<u8> for Instruction {
+    type Error = DecodeError;
+    /// Decodes one of the documented opcodes. Use `Instruction::decode` for the rest
+    fn try_from(opcode: u8) -> Result<Self, Self::Error> {
+        let instruction = NMOS_OPCODES[opcode as usize].instruction;
+        if instruction.is_documented() {
+            Ok(instruction)
+        } else {
+            Err(DecodeError::Undocumented(opcode))
         }
     }
 }
@@ -210,7 +243,7 @@ mod tests {
     use super::*;
     fn test_instruction(opcodes: &[u8], instruction: Instruction) {
         for code in opcodes {
-            assert_eq!(Instruction::from(*code), instruction)
+            assert_eq!(Instruction::decode(*code, Variant::Nmos6502), instruction)
         }
     }
     // Check that a few instructions are what we expect
@@ -240,4 +273,377 @@ mod tests {
         let opcodes = [0x20];
         test_instruction(&opcodes, Instruction::SpecialCase(SpecialCase::JSRABS));
     }
+
+    fn group_one(opcodes: &[u8], instruction: Group1Instruction) {
+        test_instruction(opcodes, Instruction::GroupOne(instruction));
+    }
+    fn group_two(opcodes: &[u8], instruction: Group2Instruction) {
+        test_instruction(opcodes, Instruction::GroupTwo(instruction));
+    }
+    fn group_three(opcodes: &[u8], instruction: Group3Instruction) {
+        test_instruction(opcodes, Instruction::GroupThree(instruction));
+    }
+
+    fn cmos(opcodes: &[u8], instruction: Instruction) {
+        for &code in opcodes {
+            assert_eq!(
+                Instruction::decode(code, Variant::Wdc65C02),
+                instruction,
+                "opcode ${:02X}",
+                code
+            );
+        }
+    }
+    fn illegal(opcodes: &[u8], instruction: IllegalInstruction) {
+        test_instruction(opcodes, Instruction::Illegal(instruction));
+    }
+
+    #[test]
+    fn documented_opcode_count() {
+        assert_eq!(
+            (0..=255)
+                .filter(|&opcode| Instruction::decode(opcode, Variant::Nmos6502).is_documented())
+                .count(),
+            151
+        );
+    }
+
+    #[test]
+    fn try_from_rejects_undocumented_opcodes() {
+        assert_eq!(
+            Instruction::try_from(0xA9),
+            Ok(Instruction::GroupOne(Group1Instruction::LDA))
+        );
+        assert_eq!(
+            Instruction::try_from(0x02),
+            Err(DecodeError::Undocumented(0x02))
+        );
+        assert_eq!(
+            (0..=255)
+                .filter(|&opcode| Instruction::try_from(opcode).is_ok())
+                .count(),
+            151
+        );
+    }
+
+    #[test]
+    fn cmos_filler_nops_are_undefined() {
+        assert_eq!(
+            Instruction::decode(0x02, Variant::Wdc65C02),
+            Instruction::Cmos(CmosInstruction::NOP)
+        );
+        assert!(!Instruction::decode(0x02, Variant::Wdc65C02).is_defined());
+        assert!(Instruction::decode(0xDA, Variant::Wdc65C02).is_defined());
+        assert!(!Instruction::decode(0xDA, Variant::Nmos6502).is_defined());
+        assert!(Instruction::decode(0xEA, Variant::Nmos6502).is_defined());
+    }
+
+    #[test]
+    fn undocumented_opcodes_are_not_documented() {
+        for opcode in [0x02, 0x04, 0x0B, 0x1A, 0x80, 0x89, 0x9C, 0x9E, 0xFF] {
+            assert!(
+                !Instruction::decode(opcode, Variant::Nmos6502).is_documented(),
+                "opcode ${:02X}",
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn read_modify_write_combinations() {
+        use IllegalInstruction::*;
+        for (instruction, column) in [(SLO, 0x00), (RLA, 0x20), (SRE, 0x40), (RRA, 0x60)] {
+            let opcodes = [0x03, 0x07, 0x0F, 0x13, 0x17, 0x1B, 0x1F].map(|code| code + column);
+            illegal(&opcodes, instruction);
+        }
+        for (instruction, column) in [(DCP, 0xC0), (ISC, 0xE0)] {
+            let opcodes = [0x03, 0x07, 0x0F, 0x13, 0x17, 0x1B, 0x1F].map(|code| code + column);
+            illegal(&opcodes, instruction);
+        }
+    }
+
+    #[test]
+    fn sax_and_lax() {
+        illegal(&[0x83, 0x87, 0x8F, 0x97], IllegalInstruction::SAX);
+        illegal(
+            &[0xA3, 0xA7, 0xAF, 0xB3, 0xB7, 0xBF],
+            IllegalInstruction::LAX,
+        );
+        illegal(&[0xBB], IllegalInstruction::LAS);
+    }
+
+    #[test]
+    fn immediate_illegals() {
+        illegal(&[0x0B, 0x2B], IllegalInstruction::ANC);
+        illegal(&[0x4B], IllegalInstruction::ALR);
+        illegal(&[0x6B], IllegalInstruction::ARR);
+        illegal(&[0x8B], IllegalInstruction::XAA);
+        illegal(&[0xAB], IllegalInstruction::LXA);
+        illegal(&[0xCB], IllegalInstruction::SBX);
+        illegal(&[0xEB], IllegalInstruction::USBC);
+    }
+
+    #[test]
+    fn high_byte_stores() {
+        illegal(&[0x93, 0x9F], IllegalInstruction::SHA);
+        illegal(&[0x9E], IllegalInstruction::SHX);
+        illegal(&[0x9C], IllegalInstruction::SHY);
+        illegal(&[0x9B], IllegalInstruction::TAS);
+    }
+
+    #[test]
+    fn illegal_nops() {
+        let opcodes = [
+            0x1A, 0x3A, 0x5A, 0x7A, 0xDA, 0xFA, 0x80, 0x82, 0x89, 0xC2, 0xE2, 0x04, 0x44, 0x64,
+            0x14, 0x34, 0x54, 0x74, 0xD4, 0xF4, 0x0C, 0x1C, 0x3C, 0x5C, 0x7C, 0xDC, 0xFC,
+        ];
+        illegal(&opcodes, IllegalInstruction::NOP);
+    }
+
+    #[test]
+    fn jam() {
+        let opcodes = [
+            0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xB2, 0xD2, 0xF2,
+        ];
+        illegal(&opcodes, IllegalInstruction::JAM);
+    }
+
+    #[test]
+    fn ora() {
+        group_one(
+            &[0x09, 0x05, 0x15, 0x0D, 0x1D, 0x19, 0x01, 0x11],
+            Group1Instruction::ORA,
+        );
+    }
+
+    #[test]
+    fn and() {
+        group_one(
+            &[0x29, 0x25, 0x35, 0x2D, 0x3D, 0x39, 0x21, 0x31],
+            Group1Instruction::AND,
+        );
+    }
+
+    #[test]
+    fn eor() {
+        group_one(
+            &[0x49, 0x45, 0x55, 0x4D, 0x5D, 0x59, 0x41, 0x51],
+            Group1Instruction::EOR,
+        );
+    }
+
+    #[test]
+    fn adc() {
+        group_one(
+            &[0x69, 0x65, 0x75, 0x6D, 0x7D, 0x79, 0x61, 0x71],
+            Group1Instruction::ADC,
+        );
+    }
+
+    #[test]
+    fn sta() {
+        group_one(
+            &[0x85, 0x95, 0x8D, 0x9D, 0x99, 0x81, 0x91],
+            Group1Instruction::STA,
+        );
+    }
+
+    #[test]
+    fn cmp() {
+        group_one(
+            &[0xC9, 0xC5, 0xD5, 0xCD, 0xDD, 0xD9, 0xC1, 0xD1],
+            Group1Instruction::CMP,
+        );
+    }
+
+    #[test]
+    fn sbc() {
+        group_one(
+            &[0xE9, 0xE5, 0xF5, 0xED, 0xFD, 0xF9, 0xE1, 0xF1],
+            Group1Instruction::SBC,
+        );
+    }
+
+    #[test]
+    fn asl() {
+        group_two(&[0x0A, 0x06, 0x16, 0x0E, 0x1E], Group2Instruction::ASL);
+    }
+
+    #[test]
+    fn rol() {
+        group_two(&[0x2A, 0x26, 0x36, 0x2E, 0x3E], Group2Instruction::ROL);
+    }
+
+    #[test]
+    fn lsr() {
+        group_two(&[0x4A, 0x46, 0x56, 0x4E, 0x5E], Group2Instruction::LSR);
+    }
+
+    #[test]
+    fn ror() {
+        group_two(&[0x6A, 0x66, 0x76, 0x6E, 0x7E], Group2Instruction::ROR);
+    }
+
+    #[test]
+    fn stx() {
+        group_two(&[0x86, 0x96, 0x8E], Group2Instruction::STX);
+    }
+
+    #[test]
+    fn ldx() {
+        group_two(&[0xA2, 0xA6, 0xB6, 0xAE, 0xBE], Group2Instruction::LDX);
+    }
+
+    #[test]
+    fn dec() {
+        group_two(&[0xC6, 0xD6, 0xCE, 0xDE], Group2Instruction::DEC);
+    }
+
+    #[test]
+    fn inc() {
+        group_two(&[0xE6, 0xF6, 0xEE, 0xFE], Group2Instruction::INC);
+    }
+
+    #[test]
+    fn bit() {
+        group_three(&[0x24, 0x2C], Group3Instruction::BIT);
+    }
+
+    #[test]
+    fn jmp() {
+        group_three(&[0x4C, 0x6C], Group3Instruction::JMP);
+    }
+
+    #[test]
+    fn sty() {
+        group_three(&[0x84, 0x94, 0x8C], Group3Instruction::STY);
+    }
+
+    #[test]
+    fn ldy() {
+        group_three(&[0xA0, 0xA4, 0xB4, 0xAC, 0xBC], Group3Instruction::LDY);
+    }
+
+    #[test]
+    fn cpy() {
+        group_three(&[0xC0, 0xC4, 0xCC], Group3Instruction::CPY);
+    }
+
+    #[test]
+    fn conditional_branches() {
+        use ConditionalBranchInstruction::*;
+        let branches = [
+            (0x10, BPL),
+            (0x30, BMI),
+            (0x50, BVC),
+            (0x70, BVS),
+            (0x90, BCC),
+            (0xB0, BCS),
+            (0xD0, BNE),
+            (0xF0, BEQ),
+        ];
+        for (opcode, branch) in branches {
+            test_instruction(&[opcode], Instruction::ConditionalBranch(branch));
+        }
+    }
+
+    #[test]
+    fn single_byte() {
+        use SingleByteInstruction::*;
+        let instructions = [
+            (0x00, BRK),
+            (0x40, RTI),
+            (0x60, RTS),
+            (0x08, PHP),
+            (0x28, PLP),
+            (0x48, PHA),
+            (0x68, PLA),
+            (0x88, DEY),
+            (0xC8, INY),
+            (0xE8, INX),
+            (0x18, CLC),
+            (0x38, SEC),
+            (0x58, CLI),
+            (0x78, SEI),
+            (0x98, TYA),
+            (0xB8, CLV),
+            (0xD8, CLD),
+            (0xF8, SED),
+            (0x8A, TXA),
+            (0x9A, TXS),
+            (0xAA, TAX),
+            (0xBA, TSX),
+            (0xCA, DEX),
+            (0xEA, NOP),
+        ];
+        for (opcode, instruction) in instructions {
+            test_instruction(&[opcode], Instruction::SingleByte(instruction));
+        }
+    }
+
+    #[test]
+    fn cmos_keeps_documented_opcodes() {
+        for opcode in 0..=255 {
+            let instruction = Instruction::decode(opcode, Variant::Nmos6502);
+            if instruction.is_documented() {
+                assert_eq!(Instruction::decode(opcode, Variant::Wdc65C02), instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn cmos_instructions() {
+        cmos(&[0x80], Instruction::Cmos(CmosInstruction::BRA));
+        cmos(&[0xDA], Instruction::Cmos(CmosInstruction::PHX));
+        cmos(&[0x5A], Instruction::Cmos(CmosInstruction::PHY));
+        cmos(&[0xFA], Instruction::Cmos(CmosInstruction::PLX));
+        cmos(&[0x7A], Instruction::Cmos(CmosInstruction::PLY));
+        cmos(
+            &[0x64, 0x74, 0x9C, 0x9E],
+            Instruction::Cmos(CmosInstruction::STZ),
+        );
+        cmos(&[0x14, 0x1C], Instruction::Cmos(CmosInstruction::TRB));
+        cmos(&[0x04, 0x0C], Instruction::Cmos(CmosInstruction::TSB));
+        cmos(&[0xCB], Instruction::Cmos(CmosInstruction::WAI));
+        cmos(&[0xDB], Instruction::Cmos(CmosInstruction::STP));
+        cmos(&[0x1A], Instruction::GroupTwo(Group2Instruction::INC));
+        cmos(&[0x3A], Instruction::GroupTwo(Group2Instruction::DEC));
+        cmos(
+            &[0x89, 0x34, 0x3C],
+            Instruction::GroupThree(Group3Instruction::BIT),
+        );
+        cmos(&[0x7C], Instruction::GroupThree(Group3Instruction::JMP));
+        cmos(&[0x12], Instruction::GroupOne(Group1Instruction::ORA));
+        cmos(&[0x92], Instruction::GroupOne(Group1Instruction::STA));
+        cmos(&[0xF2], Instruction::GroupOne(Group1Instruction::SBC));
+    }
+
+    #[test]
+    fn rockwell_bit_instructions() {
+        for bit in 0..8 {
+            let column = bit << 4;
+            cmos(
+                &[0x07 | column],
+                Instruction::Cmos(CmosInstruction::RMB(bit)),
+            );
+            cmos(
+                &[0x87 | column],
+                Instruction::Cmos(CmosInstruction::SMB(bit)),
+            );
+            cmos(
+                &[0x0F | column],
+                Instruction::Cmos(CmosInstruction::BBR(bit)),
+            );
+            cmos(
+                &[0x8F | column],
+                Instruction::Cmos(CmosInstruction::BBS(bit)),
+            );
+        }
+    }
+
+    #[test]
+    fn cmos_undefined_opcodes_are_nops() {
+        let opcodes = [0x02, 0x03, 0x0B, 0x44, 0x54, 0x5C, 0xDC, 0xE2, 0xFB];
+        cmos(&opcodes, Instruction::Cmos(CmosInstruction::NOP));
+    }
 }
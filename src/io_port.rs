@@ -0,0 +1,159 @@
+// The 6510's on-chip I/O port, as described in http://www.zimmers.net/anonftp/pub/cbm/documents/chipdata/64doc
+
+/// Address of the data direction register. A set bit makes that pin an output
+pub const DIRECTION_REGISTER: u16 = 0x0000;
+/// Address of the port's data register
+pub const DATA_REGISTER: u16 = 0x0001;
+
+/// The 6510's 8 bit I/O port, mapped over the first two bytes of memory.
+///
+/// Pins set as inputs are read from whatever drives them: a connected device, a pull-up
+/// resistor, or if neither, the charge left over from when the pin was last driven
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IoPort {
+    direction: u8,
+    output: u8,
+    // Pins driven by something outside the CPU, and the levels they are driven to
+    connected: u8,
+    external: u8,
+    pull_ups: u8,
+    // Level each floating pin was last driven to
+    floating: u8,
+}
+
+impl IoPort {
+    /// Create a new `IoPort` in its reset state, with every pin an input.
+    /// `pull_ups` has a bit set for each pin with a pull-up resistor on the board
+    pub fn new(pull_ups: u8) -> Self {
+        IoPort {
+            pull_ups,
+            ..Default::default()
+        }
+    }
+    /// The port as wired in the C64. LORAM, HIRAM and CHAREN (bits 0 to 2) and the cassette
+    /// switch sense (bit 4) are pulled up, so memory is fully banked in after a reset
+    pub fn c64() -> Self {
+        IoPort::new(0b00010111)
+    }
+    /// Value of the data direction register
+    pub fn direction(&self) -> u8 {
+        self.direction
+    }
+    /// Value written to the data register, which is only driven onto output pins
+    pub fn output(&self) -> u8 {
+        self.output
+    }
+    /// Levels on the pins, as seen by the CPU and by anything connected to the port
+    pub fn pins(&self) -> u8 {
+        let driven = self.direction | self.connected | self.pull_ups;
+        let level = (self.output & self.direction)
+            | (self.external & self.connected & !self.direction)
+            | (self.pull_ups & !self.connected & !self.direction);
+        level | (self.floating & !driven)
+    }
+    /// Drives the pins in `mask` to the levels in `levels` from outside the CPU.
+    /// Pins set as outputs ignore this until they are made inputs
+    pub fn set_input(&mut self, mask: u8, levels: u8) {
+        self.connected |= mask;
+        self.external = (self.external & !mask) | (levels & mask);
+    }
+    /// Stops driving the pins in `mask` from outside, leaving them pulled up or floating
+    pub fn disconnect_input(&mut self, mask: u8) {
+        self.latch_floating();
+        self.connected &= !mask;
+    }
+    pub fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            DIRECTION_REGISTER => self.direction,
+            _ => self.pins(),
+        }
+    }
+    pub fn write_byte(&mut self, addr: u16, byte: u8) {
+        // Floating pins keep whatever level they had before the write
+        self.latch_floating();
+        match addr {
+            DIRECTION_REGISTER => self.direction = byte,
+            _ => self.output = byte,
+        }
+    }
+    /// Every register and pin level, for save states
+    pub(crate) fn to_bytes(self) -> [u8; 6] {
+        [
+            self.direction,
+            self.output,
+            self.connected,
+            self.external,
+            self.pull_ups,
+            self.floating,
+        ]
+    }
+    pub(crate) fn from_bytes(bytes: [u8; 6]) -> Self {
+        let [direction, output, connected, external, pull_ups, floating] = bytes;
+        IoPort {
+            direction,
+            output,
+            connected,
+            external,
+            pull_ups,
+            floating,
+        }
+    }
+    // Remember the current pin levels, so pins that stop being driven hold them
+    fn latch_floating(&mut self) {
+        self.floating = self.pins();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs_read_back_written_value() {
+        let mut port = IoPort::new(0);
+        port.write_byte(DIRECTION_REGISTER, 0xFF);
+        port.write_byte(DATA_REGISTER, 0x5A);
+        assert_eq!(port.read_byte(DIRECTION_REGISTER), 0xFF);
+        assert_eq!(port.read_byte(DATA_REGISTER), 0x5A);
+    }
+
+    #[test]
+    fn inputs_read_pull_ups_and_connected_devices() {
+        let mut port = IoPort::c64();
+        assert_eq!(port.pins(), 0b00010111);
+        // Pressing play on the cassette pulls the sense line low
+        port.set_input(0b00010000, 0);
+        assert_eq!(port.pins(), 0b00000111);
+        port.disconnect_input(0b00010000);
+        assert_eq!(port.pins(), 0b00010111);
+    }
+
+    #[test]
+    fn outputs_override_pull_ups_and_inputs() {
+        let mut port = IoPort::c64();
+        port.set_input(0b00010000, 0);
+        port.write_byte(DATA_REGISTER, 0b00010000);
+        port.write_byte(DIRECTION_REGISTER, 0b00010111);
+        assert_eq!(port.pins(), 0b00010000);
+        // The latch keeps its value while the pins are inputs
+        port.write_byte(DIRECTION_REGISTER, 0);
+        assert_eq!(port.output(), 0b00010000);
+        assert_eq!(port.pins(), 0b00000111);
+    }
+
+    #[test]
+    fn floating_pins_keep_last_level() {
+        let mut port = IoPort::new(0);
+        assert_eq!(port.pins(), 0);
+        port.write_byte(DATA_REGISTER, 0b11000000);
+        port.write_byte(DIRECTION_REGISTER, 0b11000000);
+        assert_eq!(port.pins(), 0b11000000);
+        port.write_byte(DIRECTION_REGISTER, 0);
+        assert_eq!(port.pins(), 0b11000000);
+        // Driving them low again is remembered too
+        port.write_byte(DATA_REGISTER, 0);
+        port.write_byte(DIRECTION_REGISTER, 0b01000000);
+        port.write_byte(DIRECTION_REGISTER, 0);
+        assert_eq!(port.pins(), 0b10000000);
+    }
+}
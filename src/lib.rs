@@ -1,5 +1,16 @@
 pub mod addressing;
+pub mod banking;
+pub mod bus;
+pub mod cycle;
+pub mod decimal;
+pub mod devices;
+pub mod error;
 pub mod flags;
 pub mod instructions;
+pub mod io_port;
 pub mod memory;
+pub mod opcodes;
 pub mod processor;
+pub mod state;
+pub mod trace;
+pub mod variant;
@@ -1,15 +1,17 @@
 use std::fs;
 
-use emulator::{memory::Memory, processor::Processor};
+use emulator::{
+    memory::Memory,
+    processor::{Processor, StopReason},
+};
 
 fn main() {
     let program_bytes = fs::read("./test.bin").unwrap();
     let mut memory = Memory::new();
     memory.write_bytes(0x1000, &program_bytes);
-    let mut processor = Processor::new(memory);
-    // Using 0 byte for program termination for now (which corresponds to the BRK instruction)
-    while processor.peek_byte_at_pc() != 0 {
-        processor.process_next_instruction();
+    let mut processor = Processor::with_start_address(memory, 0x1000);
+    if let StopReason::Halted(error) = processor.run_until_brk() {
+        eprintln!("{}", error);
     }
     println!("{:#X?}", processor);
 }
@@ -1,4 +1,11 @@
-use std::{fmt::Debug, io::Read};
+use std::{any::Any, fmt::Debug, io::Read, ops::RangeInclusive};
+
+use crate::{
+    banking::Mapper,
+    bus::Bus,
+    io_port::{DATA_REGISTER, IoPort},
+    variant::Variant,
+};
 
 const MEMORY_SIZE: usize = 0x10000;
 impl Debug for Memory {
@@ -16,7 +23,27 @@ impl Debug for Memory {
         Ok(())
     }
 }
-pub struct Memory([u8; MEMORY_SIZE]);
+pub struct Memory {
+    bytes: [u8; MEMORY_SIZE],
+    // The 6510's I/O port, which sits over $0000 and $0001
+    io_port: Option<IoPort>,
+    // Banks ROM and RAM images over parts of RAM
+    mapper: Option<Box<dyn Mapper>>,
+    // Regions of RAM that ignore writes
+    rom: Vec<RangeInclusive<u16>>,
+    // Regions that repeat a smaller block of memory
+    mirrors: Vec<Mirror>,
+    // Regions with nothing connected, which read as whatever was last on the bus
+    unmapped: Vec<RangeInclusive<u16>>,
+    // Last value read or written over the bus
+    bus_value: u8,
+}
+
+#[derive(Clone, Debug)]
+struct Mirror {
+    range: RangeInclusive<u16>,
+    size: u16,
+}
 
 impl Default for Memory {
     fn default() -> Self {
@@ -27,26 +54,251 @@ impl Default for Memory {
 impl Memory {
     /// Create new `Memory`, initialised to 0
     pub fn new() -> Self {
-        Memory([0; MEMORY_SIZE])
+        Memory {
+            bytes: [0; MEMORY_SIZE],
+            io_port: None,
+            mapper: None,
+            rom: Vec::new(),
+            mirrors: Vec::new(),
+            unmapped: Vec::new(),
+            bus_value: 0,
+        }
+    }
+    /// Maps a 6510 I/O port over $0000 and $0001
+    pub fn attach_io_port(&mut self, io_port: IoPort) {
+        self.io_port = Some(io_port);
+    }
+    pub fn io_port(&self) -> Option<&IoPort> {
+        self.io_port.as_ref()
+    }
+    pub fn io_port_mut(&mut self) -> Option<&mut IoPort> {
+        self.io_port.as_mut()
+    }
+    /// Banks images in and out of RAM with `mapper`, replacing any mapper already attached
+    pub fn attach_mapper(&mut self, mapper: impl Mapper) {
+        self.mapper = Some(Box::new(mapper));
+    }
+    pub fn detach_mapper(&mut self) -> Option<Box<dyn Mapper>> {
+        self.mapper.take()
+    }
+    pub fn has_mapper(&self) -> bool {
+        self.mapper.is_some()
+    }
+    /// The attached mapper, if it's a `T`
+    pub fn mapper<T: Mapper>(&self) -> Option<&T> {
+        (self.mapper.as_deref()? as &dyn Any).downcast_ref()
+    }
+    pub fn mapper_mut<T: Mapper>(&mut self) -> Option<&mut T> {
+        (self.mapper.as_deref_mut()? as &mut dyn Any).downcast_mut()
+    }
+    /// Makes `range` ROM, so writes to it are ignored as on real hardware. Load the image first,
+    /// as only `mut_byte` can change it afterwards. A mapper still sees writes to ROM, so its
+    /// registers keep working and strict mode doesn't report them
+    pub fn mark_rom(&mut self, range: RangeInclusive<u16>) {
+        self.rom.push(range);
+    }
+    /// Makes all of memory writable again
+    pub fn clear_rom(&mut self) {
+        self.rom.clear();
+    }
+    pub fn is_rom(&self, addr: u16) -> bool {
+        self.resolved_is_rom(self.resolve(addr))
+    }
+    // These take an address that's already been through `resolve`
+    fn resolved_is_rom(&self, addr: u16) -> bool {
+        self.rom.iter().any(|range| range.contains(&addr))
+    }
+    fn resolved_is_unmapped(&self, addr: u16) -> bool {
+        self.unmapped.iter().any(|range| range.contains(&addr))
+    }
+    /// Repeats the first `size` bytes of `range` across the rest of it, as when a small RAM
+    /// only decodes the low address lines. For the NES, `mirror(0x0000..=0x1FFF, 0x0800)`.
+    /// Addresses are mirrored before anything else sees them
+    pub fn mirror(&mut self, range: RangeInclusive<u16>, size: u16) {
+        assert!(size > 0, "mirrored block can't be empty");
+        self.mirrors.push(Mirror { range, size });
+    }
+    /// The address that `addr` is a mirror of, or `addr` itself
+    pub fn resolve(&self, addr: u16) -> u16 {
+        match self
+            .mirrors
+            .iter()
+            .find(|mirror| mirror.range.contains(&addr))
+        {
+            Some(mirror) => {
+                let start = *mirror.range.start();
+                start + (addr - start) % mirror.size
+            }
+            None => addr,
+        }
+    }
+    /// Disconnects `range`, so writes to it are ignored and reads return the last value on the
+    /// bus (open bus). A mapper can still map images over it
+    pub fn mark_unmapped(&mut self, range: RangeInclusive<u16>) {
+        self.unmapped.push(range);
+    }
+    pub fn is_unmapped(&self, addr: u16) -> bool {
+        self.resolved_is_unmapped(self.resolve(addr))
+    }
+    /// The last value read or written over the bus, which is what unmapped addresses read as.
+    /// Only accesses through `Bus` and `write_byte` drive the bus, as `read_byte` can't
+    /// change anything. Devices in front of memory pass their values on with `Bus::drive`
+    pub fn bus_value(&self) -> u8 {
+        self.bus_value
+    }
+    pub(crate) fn set_bus_value(&mut self, value: u8) {
+        self.bus_value = value;
+    }
+    /// Moves the mapper and the ROM, mirrored and unmapped regions over from `other`, which
+    /// aren't part of save states
+    pub(crate) fn take_configuration(&mut self, other: &mut Memory) {
+        self.mapper = other.mapper.take();
+        self.rom = std::mem::take(&mut other.rom);
+        self.mirrors = std::mem::take(&mut other.mirrors);
+        self.unmapped = std::mem::take(&mut other.unmapped);
+    }
+    pub(crate) fn attached_mapper(&self) -> Option<&dyn Mapper> {
+        self.mapper.as_deref()
+    }
+    pub(crate) fn attached_mapper_mut(&mut self) -> Option<&mut dyn Mapper> {
+        self.mapper.as_deref_mut()
+    }
+    fn io_pins(&self) -> Option<u8> {
+        self.io_port.as_ref().map(IoPort::pins)
     }
     pub fn write_byte(&mut self, addr: u16, byte: u8) {
-        self.0[addr as usize] = byte;
+        self.bus_value = byte;
+        let addr = self.resolve(addr);
+        // Writes to the I/O port also reach the RAM underneath, as on the C64
+        if let Some(io_port) = &mut self.io_port
+            && addr <= DATA_REGISTER
+        {
+            io_port.write_byte(addr, byte);
+        }
+        let io_pins = self.io_pins();
+        if let Some(mapper) = &mut self.mapper
+            && mapper.write(addr, byte, io_pins)
+        {
+            return;
+        }
+        if !self.resolved_is_rom(addr) && !self.resolved_is_unmapped(addr) {
+            self.bytes[addr as usize] = byte;
+        }
     }
     pub fn write_bytes(&mut self, addr: u16, bytes: &[u8]) {
         for (i, byte) in bytes.iter().enumerate() {
-            self.0[addr as usize + i] = *byte;
+            self.write_byte(addr + i as u16, *byte);
         }
     }
     pub fn read_byte(&self, addr: u16) -> u8 {
-        self.0[addr as usize]
+        let addr = self.resolve(addr);
+        if let Some(io_port) = &self.io_port
+            && addr <= DATA_REGISTER
+        {
+            return io_port.read_byte(addr);
+        }
+        self.mapper
+            .as_ref()
+            .and_then(|mapper| mapper.read(addr, self.io_pins()))
+            .unwrap_or_else(|| {
+                if self.resolved_is_unmapped(addr) {
+                    self.bus_value
+                } else {
+                    self.bytes[addr as usize]
+                }
+            })
     }
+    /// Direct access to RAM, which bypasses the I/O port and mapper, and can change ROM
     pub fn mut_byte(&mut self, addr: u16) -> &mut u8 {
-        &mut self.0[addr as usize]
+        &mut self.bytes[addr as usize]
     }
+    /// Direct access to RAM, which bypasses the I/O port and mapper
     pub fn read_bytes(&self, addr: u16, number: u16) -> &[u8] {
-        &self.0[(addr as usize)..((addr + number) as usize)]
+        &self.bytes[(addr as usize)..((addr + number) as usize)]
+    }
+    /// All of RAM, which bypasses the I/O port
+    pub(crate) fn ram(&self) -> &[u8] {
+        &self.bytes
+    }
+    pub(crate) fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
     }
     pub fn to_bytes(&self) {
-        self.0.bytes();
+        self.bytes.bytes();
+    }
+}
+
+/// Flat RAM covering the whole address space, with the 6510's I/O port if it has one
+impl Bus for Memory {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.bus_value = self.read_byte(addr);
+        self.bus_value
+    }
+    fn write(&mut self, addr: u16, byte: u8) {
+        self.write_byte(addr, byte);
+    }
+    fn peek(&self, addr: u16) -> Option<u8> {
+        Some(self.read_byte(addr))
+    }
+    /// The mapper decides for addresses it handles, so writes to its registers are fine and
+    /// writes to its ROM images aren't. Elsewhere it's down to the ROM regions
+    fn is_read_only(&self, addr: u16) -> bool {
+        let resolved = self.resolve(addr);
+        self.mapper
+            .as_ref()
+            .and_then(|mapper| mapper.is_read_only(resolved, self.io_pins()))
+            .unwrap_or_else(|| self.resolved_is_rom(resolved))
+    }
+    /// The byte of RAM, then the I/O port's registers if the write is to the port, then the
+    /// mapper's state if the mapper has something at `addr`, each of the last two behind a flag
+    fn save_write(&self, addr: u16) -> Option<Vec<u8>> {
+        let resolved = self.resolve(addr);
+        let mut state = vec![self.bytes[resolved as usize]];
+        match self.io_port {
+            Some(io_port) if resolved <= DATA_REGISTER => {
+                state.push(1);
+                state.extend_from_slice(&io_port.to_bytes());
+            }
+            _ => state.push(0),
+        }
+        match &self.mapper {
+            Some(mapper) if mapper.is_read_only(resolved, self.io_pins()).is_some() => {
+                state.push(1);
+                state.extend_from_slice(&mapper.save_state());
+            }
+            _ => state.push(0),
+        }
+        Some(state)
+    }
+    fn restore_write(&mut self, addr: u16, state: &[u8]) {
+        let resolved = self.resolve(addr);
+        let [byte, has_io_port, rest @ ..] = state else {
+            return;
+        };
+        self.bytes[resolved as usize] = *byte;
+        let rest = match (has_io_port, rest.split_first_chunk()) {
+            (1, Some((io_port, rest))) => {
+                self.io_port = Some(IoPort::from_bytes(*io_port));
+                rest
+            }
+            _ => rest,
+        };
+        if let [1, mapper_state @ ..] = rest
+            && let Some(mapper) = &mut self.mapper
+        {
+            mapper.load_state(mapper_state);
+        }
+    }
+    fn open_bus_value(&self) -> Option<u8> {
+        Some(self.bus_value)
+    }
+    fn drive(&mut self, value: u8) {
+        self.bus_value = value;
+    }
+    /// Gives the 6510 an I/O port with no pull-ups, unless one is already attached
+    fn connect(&mut self, variant: Variant) {
+        if variant.has_io_port() && self.io_port.is_none() {
+            self.attach_io_port(IoPort::default());
+        }
     }
 }
@@ -0,0 +1,611 @@
+use crate::{
+    addressing::AddressingMode::{self, *},
+    instructions::{
+        CmosInstruction as Cm, ConditionalBranchInstruction as Br, Group1Instruction as G1,
+        Group2Instruction as G2, Group3Instruction as G3, IllegalInstruction as Il,
+        Instruction::{self, *},
+        SingleByteInstruction as Sb, SpecialCase as Sc,
+    },
+    variant::Variant,
+};
+
+/// Everything known about an opcode before it is run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Opcode {
+    pub instruction: Instruction,
+    pub addressing_mode: AddressingMode,
+    /// Number of bytes taken by the instruction, including the opcode
+    pub length: u8,
+    /// Cycles taken when no page is crossed, no branch is taken and decimal mode is off.
+    /// For instructions that halt the processor, the cycles taken before it halts
+    pub cycles: u8,
+}
+
+const fn op(instruction: Instruction, addressing_mode: AddressingMode, cycles: u8) -> Opcode {
+    Opcode {
+        instruction,
+        addressing_mode,
+        length: addressing_mode.length(),
+        cycles,
+    }
+}
+
+impl Opcode {
+    /// Looks up `opcode` as it would be run by the given `variant`
+    pub fn decode(opcode: u8, variant: Variant) -> Opcode {
+        opcode_table(variant)[opcode as usize]
+    }
+}
+
+/// The opcode table used by `variant`
+pub fn opcode_table(variant: Variant) -> &'static [Opcode; 256] {
+    if variant.has_undocumented_opcodes() {
+        &NMOS_OPCODES
+    } else {
+        &CMOS_OPCODES
+    }
+}
+
+/// Opcodes of the NMOS 6502, including the undocumented ones.
+/// Also used by the 2A03 and 6510, which only differ outside of decoding
+#[rustfmt::skip]
+pub static NMOS_OPCODES: [Opcode; 256] = [
+    /* $00 */ op(SingleByte(Sb::BRK), Implicit, 7),
+    /* $01 */ op(GroupOne(G1::ORA), IndirectX, 6),
+    /* $02 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $03 */ op(Illegal(Il::SLO), IndirectX, 8),
+    /* $04 */ op(Illegal(Il::NOP), ZeroPage, 3),
+    /* $05 */ op(GroupOne(G1::ORA), ZeroPage, 3),
+    /* $06 */ op(GroupTwo(G2::ASL), ZeroPage, 5),
+    /* $07 */ op(Illegal(Il::SLO), ZeroPage, 5),
+    /* $08 */ op(SingleByte(Sb::PHP), Implicit, 3),
+    /* $09 */ op(GroupOne(G1::ORA), Immediate, 2),
+    /* $0A */ op(GroupTwo(G2::ASL), Accumulator, 2),
+    /* $0B */ op(Illegal(Il::ANC), Immediate, 2),
+    /* $0C */ op(Illegal(Il::NOP), Absolute, 4),
+    /* $0D */ op(GroupOne(G1::ORA), Absolute, 4),
+    /* $0E */ op(GroupTwo(G2::ASL), Absolute, 6),
+    /* $0F */ op(Illegal(Il::SLO), Absolute, 6),
+    /* $10 */ op(ConditionalBranch(Br::BPL), Relative, 2),
+    /* $11 */ op(GroupOne(G1::ORA), IndirectY, 5),
+    /* $12 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $13 */ op(Illegal(Il::SLO), IndirectY, 8),
+    /* $14 */ op(Illegal(Il::NOP), ZeroPageX, 4),
+    /* $15 */ op(GroupOne(G1::ORA), ZeroPageX, 4),
+    /* $16 */ op(GroupTwo(G2::ASL), ZeroPageX, 6),
+    /* $17 */ op(Illegal(Il::SLO), ZeroPageX, 6),
+    /* $18 */ op(SingleByte(Sb::CLC), Implicit, 2),
+    /* $19 */ op(GroupOne(G1::ORA), AbsoluteY, 4),
+    /* $1A */ op(Illegal(Il::NOP), Implicit, 2),
+    /* $1B */ op(Illegal(Il::SLO), AbsoluteY, 7),
+    /* $1C */ op(Illegal(Il::NOP), AbsoluteX, 4),
+    /* $1D */ op(GroupOne(G1::ORA), AbsoluteX, 4),
+    /* $1E */ op(GroupTwo(G2::ASL), AbsoluteX, 7),
+    /* $1F */ op(Illegal(Il::SLO), AbsoluteX, 7),
+    /* $20 */ op(SpecialCase(Sc::JSRABS), Absolute, 6),
+    /* $21 */ op(GroupOne(G1::AND), IndirectX, 6),
+    /* $22 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $23 */ op(Illegal(Il::RLA), IndirectX, 8),
+    /* $24 */ op(GroupThree(G3::BIT), ZeroPage, 3),
+    /* $25 */ op(GroupOne(G1::AND), ZeroPage, 3),
+    /* $26 */ op(GroupTwo(G2::ROL), ZeroPage, 5),
+    /* $27 */ op(Illegal(Il::RLA), ZeroPage, 5),
+    /* $28 */ op(SingleByte(Sb::PLP), Implicit, 4),
+    /* $29 */ op(GroupOne(G1::AND), Immediate, 2),
+    /* $2A */ op(GroupTwo(G2::ROL), Accumulator, 2),
+    /* $2B */ op(Illegal(Il::ANC), Immediate, 2),
+    /* $2C */ op(GroupThree(G3::BIT), Absolute, 4),
+    /* $2D */ op(GroupOne(G1::AND), Absolute, 4),
+    /* $2E */ op(GroupTwo(G2::ROL), Absolute, 6),
+    /* $2F */ op(Illegal(Il::RLA), Absolute, 6),
+    /* $30 */ op(ConditionalBranch(Br::BMI), Relative, 2),
+    /* $31 */ op(GroupOne(G1::AND), IndirectY, 5),
+    /* $32 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $33 */ op(Illegal(Il::RLA), IndirectY, 8),
+    /* $34 */ op(Illegal(Il::NOP), ZeroPageX, 4),
+    /* $35 */ op(GroupOne(G1::AND), ZeroPageX, 4),
+    /* $36 */ op(GroupTwo(G2::ROL), ZeroPageX, 6),
+    /* $37 */ op(Illegal(Il::RLA), ZeroPageX, 6),
+    /* $38 */ op(SingleByte(Sb::SEC), Implicit, 2),
+    /* $39 */ op(GroupOne(G1::AND), AbsoluteY, 4),
+    /* $3A */ op(Illegal(Il::NOP), Implicit, 2),
+    /* $3B */ op(Illegal(Il::RLA), AbsoluteY, 7),
+    /* $3C */ op(Illegal(Il::NOP), AbsoluteX, 4),
+    /* $3D */ op(GroupOne(G1::AND), AbsoluteX, 4),
+    /* $3E */ op(GroupTwo(G2::ROL), AbsoluteX, 7),
+    /* $3F */ op(Illegal(Il::RLA), AbsoluteX, 7),
+    /* $40 */ op(SingleByte(Sb::RTI), Implicit, 6),
+    /* $41 */ op(GroupOne(G1::EOR), IndirectX, 6),
+    /* $42 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $43 */ op(Illegal(Il::SRE), IndirectX, 8),
+    /* $44 */ op(Illegal(Il::NOP), ZeroPage, 3),
+    /* $45 */ op(GroupOne(G1::EOR), ZeroPage, 3),
+    /* $46 */ op(GroupTwo(G2::LSR), ZeroPage, 5),
+    /* $47 */ op(Illegal(Il::SRE), ZeroPage, 5),
+    /* $48 */ op(SingleByte(Sb::PHA), Implicit, 3),
+    /* $49 */ op(GroupOne(G1::EOR), Immediate, 2),
+    /* $4A */ op(GroupTwo(G2::LSR), Accumulator, 2),
+    /* $4B */ op(Illegal(Il::ALR), Immediate, 2),
+    /* $4C */ op(GroupThree(G3::JMP), Absolute, 3),
+    /* $4D */ op(GroupOne(G1::EOR), Absolute, 4),
+    /* $4E */ op(GroupTwo(G2::LSR), Absolute, 6),
+    /* $4F */ op(Illegal(Il::SRE), Absolute, 6),
+    /* $50 */ op(ConditionalBranch(Br::BVC), Relative, 2),
+    /* $51 */ op(GroupOne(G1::EOR), IndirectY, 5),
+    /* $52 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $53 */ op(Illegal(Il::SRE), IndirectY, 8),
+    /* $54 */ op(Illegal(Il::NOP), ZeroPageX, 4),
+    /* $55 */ op(GroupOne(G1::EOR), ZeroPageX, 4),
+    /* $56 */ op(GroupTwo(G2::LSR), ZeroPageX, 6),
+    /* $57 */ op(Illegal(Il::SRE), ZeroPageX, 6),
+    /* $58 */ op(SingleByte(Sb::CLI), Implicit, 2),
+    /* $59 */ op(GroupOne(G1::EOR), AbsoluteY, 4),
+    /* $5A */ op(Illegal(Il::NOP), Implicit, 2),
+    /* $5B */ op(Illegal(Il::SRE), AbsoluteY, 7),
+    /* $5C */ op(Illegal(Il::NOP), AbsoluteX, 4),
+    /* $5D */ op(GroupOne(G1::EOR), AbsoluteX, 4),
+    /* $5E */ op(GroupTwo(G2::LSR), AbsoluteX, 7),
+    /* $5F */ op(Illegal(Il::SRE), AbsoluteX, 7),
+    /* $60 */ op(SingleByte(Sb::RTS), Implicit, 6),
+    /* $61 */ op(GroupOne(G1::ADC), IndirectX, 6),
+    /* $62 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $63 */ op(Illegal(Il::RRA), IndirectX, 8),
+    /* $64 */ op(Illegal(Il::NOP), ZeroPage, 3),
+    /* $65 */ op(GroupOne(G1::ADC), ZeroPage, 3),
+    /* $66 */ op(GroupTwo(G2::ROR), ZeroPage, 5),
+    /* $67 */ op(Illegal(Il::RRA), ZeroPage, 5),
+    /* $68 */ op(SingleByte(Sb::PLA), Implicit, 4),
+    /* $69 */ op(GroupOne(G1::ADC), Immediate, 2),
+    /* $6A */ op(GroupTwo(G2::ROR), Accumulator, 2),
+    /* $6B */ op(Illegal(Il::ARR), Immediate, 2),
+    /* $6C */ op(GroupThree(G3::JMP), Indirect, 5),
+    /* $6D */ op(GroupOne(G1::ADC), Absolute, 4),
+    /* $6E */ op(GroupTwo(G2::ROR), Absolute, 6),
+    /* $6F */ op(Illegal(Il::RRA), Absolute, 6),
+    /* $70 */ op(ConditionalBranch(Br::BVS), Relative, 2),
+    /* $71 */ op(GroupOne(G1::ADC), IndirectY, 5),
+    /* $72 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $73 */ op(Illegal(Il::RRA), IndirectY, 8),
+    /* $74 */ op(Illegal(Il::NOP), ZeroPageX, 4),
+    /* $75 */ op(GroupOne(G1::ADC), ZeroPageX, 4),
+    /* $76 */ op(GroupTwo(G2::ROR), ZeroPageX, 6),
+    /* $77 */ op(Illegal(Il::RRA), ZeroPageX, 6),
+    /* $78 */ op(SingleByte(Sb::SEI), Implicit, 2),
+    /* $79 */ op(GroupOne(G1::ADC), AbsoluteY, 4),
+    /* $7A */ op(Illegal(Il::NOP), Implicit, 2),
+    /* $7B */ op(Illegal(Il::RRA), AbsoluteY, 7),
+    /* $7C */ op(Illegal(Il::NOP), AbsoluteX, 4),
+    /* $7D */ op(GroupOne(G1::ADC), AbsoluteX, 4),
+    /* $7E */ op(GroupTwo(G2::ROR), AbsoluteX, 7),
+    /* $7F */ op(Illegal(Il::RRA), AbsoluteX, 7),
+    /* $80 */ op(Illegal(Il::NOP), Immediate, 2),
+    /* $81 */ op(GroupOne(G1::STA), IndirectX, 6),
+    /* $82 */ op(Illegal(Il::NOP), Immediate, 2),
+    /* $83 */ op(Illegal(Il::SAX), IndirectX, 6),
+    /* $84 */ op(GroupThree(G3::STY), ZeroPage, 3),
+    /* $85 */ op(GroupOne(G1::STA), ZeroPage, 3),
+    /* $86 */ op(GroupTwo(G2::STX), ZeroPage, 3),
+    /* $87 */ op(Illegal(Il::SAX), ZeroPage, 3),
+    /* $88 */ op(SingleByte(Sb::DEY), Implicit, 2),
+    /* $89 */ op(Illegal(Il::NOP), Immediate, 2),
+    /* $8A */ op(SingleByte(Sb::TXA), Implicit, 2),
+    /* $8B */ op(Illegal(Il::XAA), Immediate, 2),
+    /* $8C */ op(GroupThree(G3::STY), Absolute, 4),
+    /* $8D */ op(GroupOne(G1::STA), Absolute, 4),
+    /* $8E */ op(GroupTwo(G2::STX), Absolute, 4),
+    /* $8F */ op(Illegal(Il::SAX), Absolute, 4),
+    /* $90 */ op(ConditionalBranch(Br::BCC), Relative, 2),
+    /* $91 */ op(GroupOne(G1::STA), IndirectY, 6),
+    /* $92 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $93 */ op(Illegal(Il::SHA), IndirectY, 6),
+    /* $94 */ op(GroupThree(G3::STY), ZeroPageX, 4),
+    /* $95 */ op(GroupOne(G1::STA), ZeroPageX, 4),
+    /* $96 */ op(GroupTwo(G2::STX), ZeroPageY, 4),
+    /* $97 */ op(Illegal(Il::SAX), ZeroPageY, 4),
+    /* $98 */ op(SingleByte(Sb::TYA), Implicit, 2),
+    /* $99 */ op(GroupOne(G1::STA), AbsoluteY, 5),
+    /* $9A */ op(SingleByte(Sb::TXS), Implicit, 2),
+    /* $9B */ op(Illegal(Il::TAS), AbsoluteY, 5),
+    /* $9C */ op(Illegal(Il::SHY), AbsoluteX, 5),
+    /* $9D */ op(GroupOne(G1::STA), AbsoluteX, 5),
+    /* $9E */ op(Illegal(Il::SHX), AbsoluteY, 5),
+    /* $9F */ op(Illegal(Il::SHA), AbsoluteY, 5),
+    /* $A0 */ op(GroupThree(G3::LDY), Immediate, 2),
+    /* $A1 */ op(GroupOne(G1::LDA), IndirectX, 6),
+    /* $A2 */ op(GroupTwo(G2::LDX), Immediate, 2),
+    /* $A3 */ op(Illegal(Il::LAX), IndirectX, 6),
+    /* $A4 */ op(GroupThree(G3::LDY), ZeroPage, 3),
+    /* $A5 */ op(GroupOne(G1::LDA), ZeroPage, 3),
+    /* $A6 */ op(GroupTwo(G2::LDX), ZeroPage, 3),
+    /* $A7 */ op(Illegal(Il::LAX), ZeroPage, 3),
+    /* $A8 */ op(SingleByte(Sb::TAY), Implicit, 2),
+    /* $A9 */ op(GroupOne(G1::LDA), Immediate, 2),
+    /* $AA */ op(SingleByte(Sb::TAX), Implicit, 2),
+    /* $AB */ op(Illegal(Il::LXA), Immediate, 2),
+    /* $AC */ op(GroupThree(G3::LDY), Absolute, 4),
+    /* $AD */ op(GroupOne(G1::LDA), Absolute, 4),
+    /* $AE */ op(GroupTwo(G2::LDX), Absolute, 4),
+    /* $AF */ op(Illegal(Il::LAX), Absolute, 4),
+    /* $B0 */ op(ConditionalBranch(Br::BCS), Relative, 2),
+    /* $B1 */ op(GroupOne(G1::LDA), IndirectY, 5),
+    /* $B2 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $B3 */ op(Illegal(Il::LAX), IndirectY, 5),
+    /* $B4 */ op(GroupThree(G3::LDY), ZeroPageX, 4),
+    /* $B5 */ op(GroupOne(G1::LDA), ZeroPageX, 4),
+    /* $B6 */ op(GroupTwo(G2::LDX), ZeroPageY, 4),
+    /* $B7 */ op(Illegal(Il::LAX), ZeroPageY, 4),
+    /* $B8 */ op(SingleByte(Sb::CLV), Implicit, 2),
+    /* $B9 */ op(GroupOne(G1::LDA), AbsoluteY, 4),
+    /* $BA */ op(SingleByte(Sb::TSX), Implicit, 2),
+    /* $BB */ op(Illegal(Il::LAS), AbsoluteY, 4),
+    /* $BC */ op(GroupThree(G3::LDY), AbsoluteX, 4),
+    /* $BD */ op(GroupOne(G1::LDA), AbsoluteX, 4),
+    /* $BE */ op(GroupTwo(G2::LDX), AbsoluteY, 4),
+    /* $BF */ op(Illegal(Il::LAX), AbsoluteY, 4),
+    /* $C0 */ op(GroupThree(G3::CPY), Immediate, 2),
+    /* $C1 */ op(GroupOne(G1::CMP), IndirectX, 6),
+    /* $C2 */ op(Illegal(Il::NOP), Immediate, 2),
+    /* $C3 */ op(Illegal(Il::DCP), IndirectX, 8),
+    /* $C4 */ op(GroupThree(G3::CPY), ZeroPage, 3),
+    /* $C5 */ op(GroupOne(G1::CMP), ZeroPage, 3),
+    /* $C6 */ op(GroupTwo(G2::DEC), ZeroPage, 5),
+    /* $C7 */ op(Illegal(Il::DCP), ZeroPage, 5),
+    /* $C8 */ op(SingleByte(Sb::INY), Implicit, 2),
+    /* $C9 */ op(GroupOne(G1::CMP), Immediate, 2),
+    /* $CA */ op(SingleByte(Sb::DEX), Implicit, 2),
+    /* $CB */ op(Illegal(Il::SBX), Immediate, 2),
+    /* $CC */ op(GroupThree(G3::CPY), Absolute, 4),
+    /* $CD */ op(GroupOne(G1::CMP), Absolute, 4),
+    /* $CE */ op(GroupTwo(G2::DEC), Absolute, 6),
+    /* $CF */ op(Illegal(Il::DCP), Absolute, 6),
+    /* $D0 */ op(ConditionalBranch(Br::BNE), Relative, 2),
+    /* $D1 */ op(GroupOne(G1::CMP), IndirectY, 5),
+    /* $D2 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $D3 */ op(Illegal(Il::DCP), IndirectY, 8),
+    /* $D4 */ op(Illegal(Il::NOP), ZeroPageX, 4),
+    /* $D5 */ op(GroupOne(G1::CMP), ZeroPageX, 4),
+    /* $D6 */ op(GroupTwo(G2::DEC), ZeroPageX, 6),
+    /* $D7 */ op(Illegal(Il::DCP), ZeroPageX, 6),
+    /* $D8 */ op(SingleByte(Sb::CLD), Implicit, 2),
+    /* $D9 */ op(GroupOne(G1::CMP), AbsoluteY, 4),
+    /* $DA */ op(Illegal(Il::NOP), Implicit, 2),
+    /* $DB */ op(Illegal(Il::DCP), AbsoluteY, 7),
+    /* $DC */ op(Illegal(Il::NOP), AbsoluteX, 4),
+    /* $DD */ op(GroupOne(G1::CMP), AbsoluteX, 4),
+    /* $DE */ op(GroupTwo(G2::DEC), AbsoluteX, 7),
+    /* $DF */ op(Illegal(Il::DCP), AbsoluteX, 7),
+    /* $E0 */ op(GroupThree(G3::CPX), Immediate, 2),
+    /* $E1 */ op(GroupOne(G1::SBC), IndirectX, 6),
+    /* $E2 */ op(Illegal(Il::NOP), Immediate, 2),
+    /* $E3 */ op(Illegal(Il::ISC), IndirectX, 8),
+    /* $E4 */ op(GroupThree(G3::CPX), ZeroPage, 3),
+    /* $E5 */ op(GroupOne(G1::SBC), ZeroPage, 3),
+    /* $E6 */ op(GroupTwo(G2::INC), ZeroPage, 5),
+    /* $E7 */ op(Illegal(Il::ISC), ZeroPage, 5),
+    /* $E8 */ op(SingleByte(Sb::INX), Implicit, 2),
+    /* $E9 */ op(GroupOne(G1::SBC), Immediate, 2),
+    /* $EA */ op(SingleByte(Sb::NOP), Implicit, 2),
+    /* $EB */ op(Illegal(Il::USBC), Immediate, 2),
+    /* $EC */ op(GroupThree(G3::CPX), Absolute, 4),
+    /* $ED */ op(GroupOne(G1::SBC), Absolute, 4),
+    /* $EE */ op(GroupTwo(G2::INC), Absolute, 6),
+    /* $EF */ op(Illegal(Il::ISC), Absolute, 6),
+    /* $F0 */ op(ConditionalBranch(Br::BEQ), Relative, 2),
+    /* $F1 */ op(GroupOne(G1::SBC), IndirectY, 5),
+    /* $F2 */ op(Illegal(Il::JAM), Implicit, 2),
+    /* $F3 */ op(Illegal(Il::ISC), IndirectY, 8),
+    /* $F4 */ op(Illegal(Il::NOP), ZeroPageX, 4),
+    /* $F5 */ op(GroupOne(G1::SBC), ZeroPageX, 4),
+    /* $F6 */ op(GroupTwo(G2::INC), ZeroPageX, 6),
+    /* $F7 */ op(Illegal(Il::ISC), ZeroPageX, 6),
+    /* $F8 */ op(SingleByte(Sb::SED), Implicit, 2),
+    /* $F9 */ op(GroupOne(G1::SBC), AbsoluteY, 4),
+    /* $FA */ op(Illegal(Il::NOP), Implicit, 2),
+    /* $FB */ op(Illegal(Il::ISC), AbsoluteY, 7),
+    /* $FC */ op(Illegal(Il::NOP), AbsoluteX, 4),
+    /* $FD */ op(GroupOne(G1::SBC), AbsoluteX, 4),
+    /* $FE */ op(GroupTwo(G2::INC), AbsoluteX, 7),
+    /* $FF */ op(Illegal(Il::ISC), AbsoluteX, 7),
+];
+
+/// Opcodes of the WDC 65C02. Opcodes left undefined are all NOPs
+#[rustfmt::skip]
+pub static CMOS_OPCODES: [Opcode; 256] = [
+    /* $00 */ op(SingleByte(Sb::BRK), Implicit, 7),
+    /* $01 */ op(GroupOne(G1::ORA), IndirectX, 6),
+    /* $02 */ op(Cmos(Cm::NOP), Immediate, 2),
+    /* $03 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $04 */ op(Cmos(Cm::TSB), ZeroPage, 5),
+    /* $05 */ op(GroupOne(G1::ORA), ZeroPage, 3),
+    /* $06 */ op(GroupTwo(G2::ASL), ZeroPage, 5),
+    /* $07 */ op(Cmos(Cm::RMB(0)), ZeroPage, 5),
+    /* $08 */ op(SingleByte(Sb::PHP), Implicit, 3),
+    /* $09 */ op(GroupOne(G1::ORA), Immediate, 2),
+    /* $0A */ op(GroupTwo(G2::ASL), Accumulator, 2),
+    /* $0B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $0C */ op(Cmos(Cm::TSB), Absolute, 6),
+    /* $0D */ op(GroupOne(G1::ORA), Absolute, 4),
+    /* $0E */ op(GroupTwo(G2::ASL), Absolute, 6),
+    /* $0F */ op(Cmos(Cm::BBR(0)), ZeroPageRelative, 5),
+    /* $10 */ op(ConditionalBranch(Br::BPL), Relative, 2),
+    /* $11 */ op(GroupOne(G1::ORA), IndirectY, 5),
+    /* $12 */ op(GroupOne(G1::ORA), ZeroPageIndirect, 5),
+    /* $13 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $14 */ op(Cmos(Cm::TRB), ZeroPage, 5),
+    /* $15 */ op(GroupOne(G1::ORA), ZeroPageX, 4),
+    /* $16 */ op(GroupTwo(G2::ASL), ZeroPageX, 6),
+    /* $17 */ op(Cmos(Cm::RMB(1)), ZeroPage, 5),
+    /* $18 */ op(SingleByte(Sb::CLC), Implicit, 2),
+    /* $19 */ op(GroupOne(G1::ORA), AbsoluteY, 4),
+    /* $1A */ op(GroupTwo(G2::INC), Accumulator, 2),
+    /* $1B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $1C */ op(Cmos(Cm::TRB), Absolute, 6),
+    /* $1D */ op(GroupOne(G1::ORA), AbsoluteX, 4),
+    /* $1E */ op(GroupTwo(G2::ASL), AbsoluteX, 6),
+    /* $1F */ op(Cmos(Cm::BBR(1)), ZeroPageRelative, 5),
+    /* $20 */ op(SpecialCase(Sc::JSRABS), Absolute, 6),
+    /* $21 */ op(GroupOne(G1::AND), IndirectX, 6),
+    /* $22 */ op(Cmos(Cm::NOP), Immediate, 2),
+    /* $23 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $24 */ op(GroupThree(G3::BIT), ZeroPage, 3),
+    /* $25 */ op(GroupOne(G1::AND), ZeroPage, 3),
+    /* $26 */ op(GroupTwo(G2::ROL), ZeroPage, 5),
+    /* $27 */ op(Cmos(Cm::RMB(2)), ZeroPage, 5),
+    /* $28 */ op(SingleByte(Sb::PLP), Implicit, 4),
+    /* $29 */ op(GroupOne(G1::AND), Immediate, 2),
+    /* $2A */ op(GroupTwo(G2::ROL), Accumulator, 2),
+    /* $2B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $2C */ op(GroupThree(G3::BIT), Absolute, 4),
+    /* $2D */ op(GroupOne(G1::AND), Absolute, 4),
+    /* $2E */ op(GroupTwo(G2::ROL), Absolute, 6),
+    /* $2F */ op(Cmos(Cm::BBR(2)), ZeroPageRelative, 5),
+    /* $30 */ op(ConditionalBranch(Br::BMI), Relative, 2),
+    /* $31 */ op(GroupOne(G1::AND), IndirectY, 5),
+    /* $32 */ op(GroupOne(G1::AND), ZeroPageIndirect, 5),
+    /* $33 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $34 */ op(GroupThree(G3::BIT), ZeroPageX, 4),
+    /* $35 */ op(GroupOne(G1::AND), ZeroPageX, 4),
+    /* $36 */ op(GroupTwo(G2::ROL), ZeroPageX, 6),
+    /* $37 */ op(Cmos(Cm::RMB(3)), ZeroPage, 5),
+    /* $38 */ op(SingleByte(Sb::SEC), Implicit, 2),
+    /* $39 */ op(GroupOne(G1::AND), AbsoluteY, 4),
+    /* $3A */ op(GroupTwo(G2::DEC), Accumulator, 2),
+    /* $3B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $3C */ op(GroupThree(G3::BIT), AbsoluteX, 4),
+    /* $3D */ op(GroupOne(G1::AND), AbsoluteX, 4),
+    /* $3E */ op(GroupTwo(G2::ROL), AbsoluteX, 6),
+    /* $3F */ op(Cmos(Cm::BBR(3)), ZeroPageRelative, 5),
+    /* $40 */ op(SingleByte(Sb::RTI), Implicit, 6),
+    /* $41 */ op(GroupOne(G1::EOR), IndirectX, 6),
+    /* $42 */ op(Cmos(Cm::NOP), Immediate, 2),
+    /* $43 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $44 */ op(Cmos(Cm::NOP), ZeroPage, 3),
+    /* $45 */ op(GroupOne(G1::EOR), ZeroPage, 3),
+    /* $46 */ op(GroupTwo(G2::LSR), ZeroPage, 5),
+    /* $47 */ op(Cmos(Cm::RMB(4)), ZeroPage, 5),
+    /* $48 */ op(SingleByte(Sb::PHA), Implicit, 3),
+    /* $49 */ op(GroupOne(G1::EOR), Immediate, 2),
+    /* $4A */ op(GroupTwo(G2::LSR), Accumulator, 2),
+    /* $4B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $4C */ op(GroupThree(G3::JMP), Absolute, 3),
+    /* $4D */ op(GroupOne(G1::EOR), Absolute, 4),
+    /* $4E */ op(GroupTwo(G2::LSR), Absolute, 6),
+    /* $4F */ op(Cmos(Cm::BBR(4)), ZeroPageRelative, 5),
+    /* $50 */ op(ConditionalBranch(Br::BVC), Relative, 2),
+    /* $51 */ op(GroupOne(G1::EOR), IndirectY, 5),
+    /* $52 */ op(GroupOne(G1::EOR), ZeroPageIndirect, 5),
+    /* $53 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $54 */ op(Cmos(Cm::NOP), ZeroPageX, 4),
+    /* $55 */ op(GroupOne(G1::EOR), ZeroPageX, 4),
+    /* $56 */ op(GroupTwo(G2::LSR), ZeroPageX, 6),
+    /* $57 */ op(Cmos(Cm::RMB(5)), ZeroPage, 5),
+    /* $58 */ op(SingleByte(Sb::CLI), Implicit, 2),
+    /* $59 */ op(GroupOne(G1::EOR), AbsoluteY, 4),
+    /* $5A */ op(Cmos(Cm::PHY), Implicit, 3),
+    /* $5B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $5C */ op(Cmos(Cm::NOP), Absolute, 8),
+    /* $5D */ op(GroupOne(G1::EOR), AbsoluteX, 4),
+    /* $5E */ op(GroupTwo(G2::LSR), AbsoluteX, 6),
+    /* $5F */ op(Cmos(Cm::BBR(5)), ZeroPageRelative, 5),
+    /* $60 */ op(SingleByte(Sb::RTS), Implicit, 6),
+    /* $61 */ op(GroupOne(G1::ADC), IndirectX, 6),
+    /* $62 */ op(Cmos(Cm::NOP), Immediate, 2),
+    /* $63 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $64 */ op(Cmos(Cm::STZ), ZeroPage, 3),
+    /* $65 */ op(GroupOne(G1::ADC), ZeroPage, 3),
+    /* $66 */ op(GroupTwo(G2::ROR), ZeroPage, 5),
+    /* $67 */ op(Cmos(Cm::RMB(6)), ZeroPage, 5),
+    /* $68 */ op(SingleByte(Sb::PLA), Implicit, 4),
+    /* $69 */ op(GroupOne(G1::ADC), Immediate, 2),
+    /* $6A */ op(GroupTwo(G2::ROR), Accumulator, 2),
+    /* $6B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $6C */ op(GroupThree(G3::JMP), Indirect, 6),
+    /* $6D */ op(GroupOne(G1::ADC), Absolute, 4),
+    /* $6E */ op(GroupTwo(G2::ROR), Absolute, 6),
+    /* $6F */ op(Cmos(Cm::BBR(6)), ZeroPageRelative, 5),
+    /* $70 */ op(ConditionalBranch(Br::BVS), Relative, 2),
+    /* $71 */ op(GroupOne(G1::ADC), IndirectY, 5),
+    /* $72 */ op(GroupOne(G1::ADC), ZeroPageIndirect, 5),
+    /* $73 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $74 */ op(Cmos(Cm::STZ), ZeroPageX, 4),
+    /* $75 */ op(GroupOne(G1::ADC), ZeroPageX, 4),
+    /* $76 */ op(GroupTwo(G2::ROR), ZeroPageX, 6),
+    /* $77 */ op(Cmos(Cm::RMB(7)), ZeroPage, 5),
+    /* $78 */ op(SingleByte(Sb::SEI), Implicit, 2),
+    /* $79 */ op(GroupOne(G1::ADC), AbsoluteY, 4),
+    /* $7A */ op(Cmos(Cm::PLY), Implicit, 4),
+    /* $7B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $7C */ op(GroupThree(G3::JMP), AbsoluteIndexedIndirect, 6),
+    /* $7D */ op(GroupOne(G1::ADC), AbsoluteX, 4),
+    /* $7E */ op(GroupTwo(G2::ROR), AbsoluteX, 6),
+    /* $7F */ op(Cmos(Cm::BBR(7)), ZeroPageRelative, 5),
+    /* $80 */ op(Cmos(Cm::BRA), Relative, 3),
+    /* $81 */ op(GroupOne(G1::STA), IndirectX, 6),
+    /* $82 */ op(Cmos(Cm::NOP), Immediate, 2),
+    /* $83 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $84 */ op(GroupThree(G3::STY), ZeroPage, 3),
+    /* $85 */ op(GroupOne(G1::STA), ZeroPage, 3),
+    /* $86 */ op(GroupTwo(G2::STX), ZeroPage, 3),
+    /* $87 */ op(Cmos(Cm::SMB(0)), ZeroPage, 5),
+    /* $88 */ op(SingleByte(Sb::DEY), Implicit, 2),
+    /* $89 */ op(GroupThree(G3::BIT), Immediate, 2),
+    /* $8A */ op(SingleByte(Sb::TXA), Implicit, 2),
+    /* $8B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $8C */ op(GroupThree(G3::STY), Absolute, 4),
+    /* $8D */ op(GroupOne(G1::STA), Absolute, 4),
+    /* $8E */ op(GroupTwo(G2::STX), Absolute, 4),
+    /* $8F */ op(Cmos(Cm::BBS(0)), ZeroPageRelative, 5),
+    /* $90 */ op(ConditionalBranch(Br::BCC), Relative, 2),
+    /* $91 */ op(GroupOne(G1::STA), IndirectY, 6),
+    /* $92 */ op(GroupOne(G1::STA), ZeroPageIndirect, 5),
+    /* $93 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $94 */ op(GroupThree(G3::STY), ZeroPageX, 4),
+    /* $95 */ op(GroupOne(G1::STA), ZeroPageX, 4),
+    /* $96 */ op(GroupTwo(G2::STX), ZeroPageY, 4),
+    /* $97 */ op(Cmos(Cm::SMB(1)), ZeroPage, 5),
+    /* $98 */ op(SingleByte(Sb::TYA), Implicit, 2),
+    /* $99 */ op(GroupOne(G1::STA), AbsoluteY, 5),
+    /* $9A */ op(SingleByte(Sb::TXS), Implicit, 2),
+    /* $9B */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $9C */ op(Cmos(Cm::STZ), Absolute, 4),
+    /* $9D */ op(GroupOne(G1::STA), AbsoluteX, 5),
+    /* $9E */ op(Cmos(Cm::STZ), AbsoluteX, 5),
+    /* $9F */ op(Cmos(Cm::BBS(1)), ZeroPageRelative, 5),
+    /* $A0 */ op(GroupThree(G3::LDY), Immediate, 2),
+    /* $A1 */ op(GroupOne(G1::LDA), IndirectX, 6),
+    /* $A2 */ op(GroupTwo(G2::LDX), Immediate, 2),
+    /* $A3 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $A4 */ op(GroupThree(G3::LDY), ZeroPage, 3),
+    /* $A5 */ op(GroupOne(G1::LDA), ZeroPage, 3),
+    /* $A6 */ op(GroupTwo(G2::LDX), ZeroPage, 3),
+    /* $A7 */ op(Cmos(Cm::SMB(2)), ZeroPage, 5),
+    /* $A8 */ op(SingleByte(Sb::TAY), Implicit, 2),
+    /* $A9 */ op(GroupOne(G1::LDA), Immediate, 2),
+    /* $AA */ op(SingleByte(Sb::TAX), Implicit, 2),
+    /* $AB */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $AC */ op(GroupThree(G3::LDY), Absolute, 4),
+    /* $AD */ op(GroupOne(G1::LDA), Absolute, 4),
+    /* $AE */ op(GroupTwo(G2::LDX), Absolute, 4),
+    /* $AF */ op(Cmos(Cm::BBS(2)), ZeroPageRelative, 5),
+    /* $B0 */ op(ConditionalBranch(Br::BCS), Relative, 2),
+    /* $B1 */ op(GroupOne(G1::LDA), IndirectY, 5),
+    /* $B2 */ op(GroupOne(G1::LDA), ZeroPageIndirect, 5),
+    /* $B3 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $B4 */ op(GroupThree(G3::LDY), ZeroPageX, 4),
+    /* $B5 */ op(GroupOne(G1::LDA), ZeroPageX, 4),
+    /* $B6 */ op(GroupTwo(G2::LDX), ZeroPageY, 4),
+    /* $B7 */ op(Cmos(Cm::SMB(3)), ZeroPage, 5),
+    /* $B8 */ op(SingleByte(Sb::CLV), Implicit, 2),
+    /* $B9 */ op(GroupOne(G1::LDA), AbsoluteY, 4),
+    /* $BA */ op(SingleByte(Sb::TSX), Implicit, 2),
+    /* $BB */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $BC */ op(GroupThree(G3::LDY), AbsoluteX, 4),
+    /* $BD */ op(GroupOne(G1::LDA), AbsoluteX, 4),
+    /* $BE */ op(GroupTwo(G2::LDX), AbsoluteY, 4),
+    /* $BF */ op(Cmos(Cm::BBS(3)), ZeroPageRelative, 5),
+    /* $C0 */ op(GroupThree(G3::CPY), Immediate, 2),
+    /* $C1 */ op(GroupOne(G1::CMP), IndirectX, 6),
+    /* $C2 */ op(Cmos(Cm::NOP), Immediate, 2),
+    /* $C3 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $C4 */ op(GroupThree(G3::CPY), ZeroPage, 3),
+    /* $C5 */ op(GroupOne(G1::CMP), ZeroPage, 3),
+    /* $C6 */ op(GroupTwo(G2::DEC), ZeroPage, 5),
+    /* $C7 */ op(Cmos(Cm::SMB(4)), ZeroPage, 5),
+    /* $C8 */ op(SingleByte(Sb::INY), Implicit, 2),
+    /* $C9 */ op(GroupOne(G1::CMP), Immediate, 2),
+    /* $CA */ op(SingleByte(Sb::DEX), Implicit, 2),
+    /* $CB */ op(Cmos(Cm::WAI), Implicit, 3),
+    /* $CC */ op(GroupThree(G3::CPY), Absolute, 4),
+    /* $CD */ op(GroupOne(G1::CMP), Absolute, 4),
+    /* $CE */ op(GroupTwo(G2::DEC), Absolute, 6),
+    /* $CF */ op(Cmos(Cm::BBS(4)), ZeroPageRelative, 5),
+    /* $D0 */ op(ConditionalBranch(Br::BNE), Relative, 2),
+    /* $D1 */ op(GroupOne(G1::CMP), IndirectY, 5),
+    /* $D2 */ op(GroupOne(G1::CMP), ZeroPageIndirect, 5),
+    /* $D3 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $D4 */ op(Cmos(Cm::NOP), ZeroPageX, 4),
+    /* $D5 */ op(GroupOne(G1::CMP), ZeroPageX, 4),
+    /* $D6 */ op(GroupTwo(G2::DEC), ZeroPageX, 6),
+    /* $D7 */ op(Cmos(Cm::SMB(5)), ZeroPage, 5),
+    /* $D8 */ op(SingleByte(Sb::CLD), Implicit, 2),
+    /* $D9 */ op(GroupOne(G1::CMP), AbsoluteY, 4),
+    /* $DA */ op(Cmos(Cm::PHX), Implicit, 3),
+    /* $DB */ op(Cmos(Cm::STP), Implicit, 3),
+    /* $DC */ op(Cmos(Cm::NOP), Absolute, 4),
+    /* $DD */ op(GroupOne(G1::CMP), AbsoluteX, 4),
+    /* $DE */ op(GroupTwo(G2::DEC), AbsoluteX, 7),
+    /* $DF */ op(Cmos(Cm::BBS(5)), ZeroPageRelative, 5),
+    /* $E0 */ op(GroupThree(G3::CPX), Immediate, 2),
+    /* $E1 */ op(GroupOne(G1::SBC), IndirectX, 6),
+    /* $E2 */ op(Cmos(Cm::NOP), Immediate, 2),
+    /* $E3 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $E4 */ op(GroupThree(G3::CPX), ZeroPage, 3),
+    /* $E5 */ op(GroupOne(G1::SBC), ZeroPage, 3),
+    /* $E6 */ op(GroupTwo(G2::INC), ZeroPage, 5),
+    /* $E7 */ op(Cmos(Cm::SMB(6)), ZeroPage, 5),
+    /* $E8 */ op(SingleByte(Sb::INX), Implicit, 2),
+    /* $E9 */ op(GroupOne(G1::SBC), Immediate, 2),
+    /* $EA */ op(SingleByte(Sb::NOP), Implicit, 2),
+    /* $EB */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $EC */ op(GroupThree(G3::CPX), Absolute, 4),
+    /* $ED */ op(GroupOne(G1::SBC), Absolute, 4),
+    /* $EE */ op(GroupTwo(G2::INC), Absolute, 6),
+    /* $EF */ op(Cmos(Cm::BBS(6)), ZeroPageRelative, 5),
+    /* $F0 */ op(ConditionalBranch(Br::BEQ), Relative, 2),
+    /* $F1 */ op(GroupOne(G1::SBC), IndirectY, 5),
+    /* $F2 */ op(GroupOne(G1::SBC), ZeroPageIndirect, 5),
+    /* $F3 */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $F4 */ op(Cmos(Cm::NOP), ZeroPageX, 4),
+    /* $F5 */ op(GroupOne(G1::SBC), ZeroPageX, 4),
+    /* $F6 */ op(GroupTwo(G2::INC), ZeroPageX, 6),
+    /* $F7 */ op(Cmos(Cm::SMB(7)), ZeroPage, 5),
+    /* $F8 */ op(SingleByte(Sb::SED), Implicit, 2),
+    /* $F9 */ op(GroupOne(G1::SBC), AbsoluteY, 4),
+    /* $FA */ op(Cmos(Cm::PLX), Implicit, 4),
+    /* $FB */ op(Cmos(Cm::NOP), Implicit, 1),
+    /* $FC */ op(Cmos(Cm::NOP), Absolute, 4),
+    /* $FD */ op(GroupOne(G1::SBC), AbsoluteX, 4),
+    /* $FE */ op(GroupTwo(G2::INC), AbsoluteX, 7),
+    /* $FF */ op(Cmos(Cm::BBS(7)), ZeroPageRelative, 5),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documented_opcodes_decode_the_same_on_cmos() {
+        for (nmos, cmos) in NMOS_OPCODES.iter().zip(CMOS_OPCODES.iter()) {
+            if nmos.instruction.is_documented() {
+                assert_eq!(
+                    (cmos.instruction, cmos.addressing_mode, cmos.length),
+                    (nmos.instruction, nmos.addressing_mode, nmos.length)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        assert_eq!(NMOS_OPCODES[0x00].length, 1);
+        assert_eq!(NMOS_OPCODES[0xA9].length, 2);
+        assert_eq!(NMOS_OPCODES[0x20].length, 3);
+        assert_eq!(NMOS_OPCODES[0x6C].length, 3);
+        assert_eq!(NMOS_OPCODES[0x1C].length, 3);
+        assert_eq!(CMOS_OPCODES[0x1C].length, 3);
+        assert_eq!(CMOS_OPCODES[0x0F].length, 3);
+        assert_eq!(CMOS_OPCODES[0x03].length, 1);
+    }
+
+    #[test]
+    fn variants_share_nmos_table() {
+        for variant in Variant::ALL {
+            let expected: &[Opcode; 256] = if variant.is_cmos() {
+                &CMOS_OPCODES
+            } else {
+                &NMOS_OPCODES
+            };
+            assert!(std::ptr::eq(opcode_table(variant), expected));
+        }
+    }
+}
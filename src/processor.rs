@@ -1,17 +1,31 @@
-use std::fmt::Debug;
+use std::{collections::BTreeSet, fmt::Debug};
 
 use crate::{
     addressing::AddressingMode,
-    flags::Flags,
+    bus::Bus,
+    cycle::{BusAccess, BusCycle, Replay, ReplayedAccess},
+    decimal::{self, DecimalResult},
+    error::CpuError,
+    flags::{Flags, StatusFlag},
     instructions::{
-        ConditionalBranchInstruction, Group1Instruction, Group2Instruction, Group3Instruction,
-        Instruction, SingleByteInstruction, SpecialCase,
+        CmosInstruction, ConditionalBranchInstruction, Group1Instruction, Group2Instruction,
+        Group3Instruction, IllegalInstruction, Instruction, OperandAccess, SingleByteInstruction,
+        SpecialCase,
     },
     memory::Memory,
+    opcodes::Opcode,
+    state::CpuState,
+    trace::{TraceEvent, Tracer},
+    variant::Variant,
 };
+
+mod history;
+mod save_state;
+
 #[derive(Debug)]
-pub struct Processor {
-    pub memory: Memory,
+pub struct Processor<B: Bus = Memory> {
+    pub memory: B,
+    variant: Variant,
     // Registers
     a: u8, // Accumulator
     x: u8,
@@ -20,348 +34,1201 @@ pub struct Processor {
     // Adress in stack is 0x0100 + SP
     s: u8,   // Stack pointer
     pc: u16, // Program counter
+    // Interrupt lines, `true` while asserted
+    irq_line: bool,
+    nmi_line: bool,
+    // Set on the asserting edge of NMI and cleared once it has been serviced
+    nmi_pending: bool,
+    // Value of the I flag when interrupts were last polled. CLI, SEI and PLP change the flag
+    // after polling, so their effect on IRQs is delayed by an instruction
+    irq_disabled_at_poll: bool,
+    // Total number of cycles run. Every cycle performs exactly one bus access
+    cycles: u64,
+    // Instruction currently being run a cycle at a time by `step_cycle`
+    cycle_step: Option<CycleStep>,
+    // Set while re-running a partially stepped instruction
+    replay: Option<Replay>,
+    // Address of the JAM (or STP on the 65C02) that locked up the processor.
+    // Nothing but a reset will get it going again
+    jammed: Option<u16>,
+    // Set by WAI on the 65C02 until an interrupt line is asserted
+    waiting: bool,
+    // Constant ORed into A by the unstable XAA and LXA instructions
+    magic_constant: u8,
+    undefined_opcode_policy: UndefinedOpcodePolicy<B>,
+    tracer: Option<Box<dyn Tracer>>,
+    // Addresses the run methods stop at
+    breakpoints: BTreeSet<u16>,
+    // Undo records for stepping backwards, if enabled
+    history: Option<history::History>,
+    // Whether writes to ROM are reported as errors
+    strict_rom: bool,
+    // Address and value of the first write to ROM by the current instruction, when strict
+    rom_write: Option<(u16, u8)>,
+}
+/// State from the start of an instruction being run a cycle at a time.
+/// The registers are restored before each re-run of the instruction
+#[derive(Debug)]
+struct CycleStep {
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    s: u8,
+    pc: u16,
+    irq_disabled_at_poll: bool,
+    cycles: u64,
+    jammed: Option<u16>,
+    waiting: bool,
+    // Interrupt being serviced instead of an instruction, decided on the first cycle
+    interrupt: Option<u16>,
+    completed: Vec<BusCycle>,
+}
+/// Address of the NMI handler
+pub const NMI_VECTOR: u16 = 0xFFFA;
+/// Address of the RESET handler
+pub const RESET_VECTOR: u16 = 0xFFFC;
+/// Address of the IRQ and BRK handler
+pub const IRQ_VECTOR: u16 = 0xFFFE;
+/// Magic constant used by XAA and LXA unless another is set. The value depends on the chip
+/// and even its temperature, but $EE is the most commonly observed one
+pub const DEFAULT_MAGIC_CONSTANT: u8 = 0xEE;
+/// Called by [`UndefinedOpcodePolicy::Trap`] with the address and value of the opcode.
+/// The PC has already been moved past the instruction's operands
+pub type TrapHandler<B = Memory> = fn(&mut Processor<B>, u16, u8);
+/// What the processor does when it meets an opcode without a defined behaviour: one of the
+/// NMOS undocumented opcodes, or one of the 65C02's filler NOPs
+#[derive(Default)]
+pub enum UndefinedOpcodePolicy<B: Bus = Memory> {
+    /// Run it as the variant's hardware would
+    #[default]
+    Execute,
+    /// Stop with [`CpuError::UnknownOpcode`], leaving the PC on the opcode
+    Halt,
+    /// Skip it as a NOP, taking a cycle for each byte of the instruction
+    Nop,
+    /// Skip it like [`UndefinedOpcodePolicy::Nop`], then call the handler
+    Trap(TrapHandler<B>),
+    /// Lock up until reset, like the NMOS JAM opcodes
+    Jam,
+}
+// Implemented by hand, as deriving would need `B` itself to be `Clone`, `Copy` and `Debug`
+impl<B: Bus> Clone for UndefinedOpcodePolicy<B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<B: Bus> Copy for UndefinedOpcodePolicy<B> {}
+impl<B: Bus> Debug for UndefinedOpcodePolicy<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UndefinedOpcodePolicy::Execute => write!(f, "Execute"),
+            UndefinedOpcodePolicy::Halt => write!(f, "Halt"),
+            UndefinedOpcodePolicy::Nop => write!(f, "Nop"),
+            UndefinedOpcodePolicy::Trap(handler) => write!(f, "Trap({:p})", *handler),
+            UndefinedOpcodePolicy::Jam => write!(f, "Jam"),
+        }
+    }
 }
-fn interpret_as_signed(v: u8) -> i8 {
-    // Safe as we're converting a u8 into an i8, which is always valid
-    unsafe { std::mem::transmute(v) }
+/// Why one of the run methods, such as [`Processor::run_until`], returned
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The requested number of instructions were run
+    StepsCompleted,
+    /// The cycle budget was used up
+    CyclesElapsed,
+    /// The predicate returned `true`
+    Predicate,
+    /// The PC reached a breakpoint at this address. The instruction there hasn't been run
+    Breakpoint(u16),
+    /// The PC reached a BRK instruction, which hasn't been run
+    Brk,
+    /// An instruction couldn't be run, or the processor jammed
+    Halted(CpuError),
 }
-impl Processor {
-    /// Initialises a new `Processor` in its RESET state
-    pub fn new(memory: Memory) -> Processor {
-        Processor {
+impl<B: Bus> Processor<B> {
+    /// Powers on a new NMOS `Processor`. Registers start cleared and the RESET sequence is run,
+    /// so execution begins at the address stored in the RESET vector with the stack pointer at $FD
+    pub fn new(memory: B) -> Self {
+        Processor::with_variant(memory, Variant::default())
+    }
+    /// Powers on a new `Processor` like [`Processor::new`], emulating the given `variant`.
+    /// The bus is told about the variant first, which gives a 6510's `Memory` its I/O port
+    pub fn with_variant(mut memory: B, variant: Variant) -> Self {
+        memory.connect(variant);
+        let mut processor = Processor {
             memory,
+            variant,
             a: 0,
             x: 0,
             y: 0,
             p: Flags::new(),
-            s: 0x00FF,
-            // A bit after the zero page and stack
-            pc: 0x1000,
+            s: 0,
+            pc: 0,
+            irq_line: false,
+            nmi_line: false,
+            nmi_pending: false,
+            irq_disabled_at_poll: false,
+            cycles: 0,
+            cycle_step: None,
+            replay: None,
+            jammed: None,
+            waiting: false,
+            magic_constant: DEFAULT_MAGIC_CONSTANT,
+            undefined_opcode_policy: UndefinedOpcodePolicy::default(),
+            tracer: None,
+            breakpoints: BTreeSet::new(),
+            history: None,
+            strict_rom: false,
+            rom_write: None,
+        };
+        processor.reset();
+        processor
+    }
+    /// Powers on a new `Processor` like [`Processor::new`], but starts executing at `pc`
+    /// rather than the RESET vector. Useful for raw binaries that don't contain vectors
+    pub fn with_start_address(memory: B, pc: u16) -> Self {
+        let mut processor = Processor::new(memory);
+        processor.pc = pc;
+        processor
+    }
+    /// Asserts or releases the IRQ line. IRQ is level-triggered, so it will keep being
+    /// serviced for as long as it is asserted and the I flag is clear
+    pub fn set_irq_line(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+    /// Asserts or releases the NMI line. NMI is edge-triggered, so it is serviced once each
+    /// time the line goes from released to asserted
+    pub fn set_nmi_line(&mut self, asserted: bool) {
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
         }
+        self.nmi_line = asserted;
     }
-    pub fn add_to_pc(&mut self, num: i8) {
-        let abs = num.unsigned_abs();
-        if num < 0 {
-            self.pc -= abs as u16
+    /// Pulses the NMI line, causing a single NMI
+    pub fn trigger_nmi(&mut self) {
+        self.set_nmi_line(true);
+        self.set_nmi_line(false);
+    }
+    /// Performs the RESET sequence: the PC is loaded from the RESET vector and interrupts are
+    /// disabled. The stack pointer is decremented by three, as the stack pushes of an interrupt
+    /// are performed with writes suppressed
+    pub fn reset(&mut self) {
+        self.read_byte(self.pc);
+        self.read_byte(self.pc);
+        for _ in 0..3 {
+            self.read_stack_pointer();
+            self.s = self.s.wrapping_sub(1);
+        }
+        self.p.set(StatusFlag::InterruptDisable);
+        if self.variant.clears_decimal_on_interrupt() {
+            self.p.clear(StatusFlag::Decimal);
+        }
+        self.irq_disabled_at_poll = true;
+        self.nmi_pending = false;
+        self.jammed = None;
+        self.waiting = false;
+        self.pc = self.read_word(RESET_VECTOR);
+    }
+    /// The model of 6502 being emulated
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+    /// Whether a JAM instruction (or STP on the 65C02) has locked up the processor. Once jammed,
+    /// it ignores interrupts and only reads from $FFFF until it is reset
+    pub fn is_jammed(&self) -> bool {
+        self.jammed.is_some()
+    }
+    /// Whether a WAI instruction is waiting for an interrupt line to be asserted
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+    /// Constant used by the unstable XAA and LXA instructions, which compute
+    /// `(A | magic) & operand` (also ANDed with X for XAA)
+    pub fn magic_constant(&self) -> u8 {
+        self.magic_constant
+    }
+    /// Sets the constant used by XAA and LXA, to match a particular chip
+    pub fn set_magic_constant(&mut self, magic_constant: u8) {
+        self.magic_constant = magic_constant;
+    }
+    /// What happens when an undefined opcode is run
+    pub fn undefined_opcode_policy(&self) -> UndefinedOpcodePolicy<B> {
+        self.undefined_opcode_policy
+    }
+    /// Sets what happens when an undefined opcode is run. The non-fatal policies are useful
+    /// for fuzzing, or for exploring ROMs that may run into data
+    pub fn set_undefined_opcode_policy(&mut self, policy: UndefinedOpcodePolicy<B>) {
+        self.undefined_opcode_policy = policy;
+    }
+    /// Whether writes to ROM are reported as errors
+    pub fn strict_rom(&self) -> bool {
+        self.strict_rom
+    }
+    /// When strict, an instruction that writes to ROM makes `process_next_instruction` return
+    /// [`CpuError::RomWrite`] once it finishes, and the run methods stop. The write itself is
+    /// still ignored by the bus
+    pub fn set_strict_rom(&mut self, strict: bool) {
+        self.strict_rom = strict;
+    }
+    /// Sets a tracer to be called for every instruction run, returning the previous one
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Tracer>>) -> Option<Box<dyn Tracer>> {
+        std::mem::replace(&mut self.tracer, tracer)
+    }
+    /// Snapshot of the registers
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            p: *self.p.raw(),
+            s: self.s,
+            pc: self.pc,
+        }
+    }
+    /// Sets every register from a snapshot
+    pub fn set_state(&mut self, state: CpuState) {
+        self.a = state.a;
+        self.x = state.x;
+        self.y = state.y;
+        *self.p.raw_mut() = state.p;
+        self.s = state.s;
+        self.pc = state.pc;
+    }
+    /// Accumulator
+    pub fn a(&self) -> u8 {
+        self.a
+    }
+    pub fn set_a(&mut self, a: u8) {
+        self.a = a;
+    }
+    pub fn x(&self) -> u8 {
+        self.x
+    }
+    pub fn set_x(&mut self, x: u8) {
+        self.x = x;
+    }
+    pub fn y(&self) -> u8 {
+        self.y
+    }
+    pub fn set_y(&mut self, y: u8) {
+        self.y = y;
+    }
+    /// Status register
+    pub fn p(&self) -> u8 {
+        *self.p.raw()
+    }
+    pub fn set_p(&mut self, p: u8) {
+        *self.p.raw_mut() = p;
+    }
+    /// Stack pointer. The stack is at $0100 + S
+    pub fn s(&self) -> u8 {
+        self.s
+    }
+    pub fn set_s(&mut self, s: u8) {
+        self.s = s;
+    }
+    /// Program counter
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+    /// Pushes the return address and status, then jumps through `vector`.
+    /// `break_flag` is set in the pushed status for BRK, and clear for IRQ and NMI
+    fn interrupt(&mut self, return_address: u16, vector: u16, break_flag: bool) {
+        self.push_word_to_stack(return_address);
+        self.push_to_stack(self.p.to_pushed(break_flag));
+        self.p.set(StatusFlag::InterruptDisable);
+        // The 65C02 leaves decimal mode, so handlers don't have to
+        if self.variant.clears_decimal_on_interrupt() {
+            self.p.clear(StatusFlag::Decimal);
+        }
+        self.pc = self.read_word(vector);
+    }
+    /// Checks for an interrupt to service before the next instruction, returning its vector
+    fn poll_interrupts(&mut self) -> Option<u16> {
+        if self.waiting {
+            // WAI stops waiting once a line is asserted, even if the I flag masks the IRQ
+            if !self.nmi_pending && !self.irq_line {
+                return None;
+            }
+            self.waiting = false;
+        }
+        if self.is_jammed() {
+            None
+        } else if self.nmi_pending {
+            self.nmi_pending = false;
+            Some(NMI_VECTOR)
+        } else if self.irq_line && !self.irq_disabled_at_poll {
+            Some(IRQ_VECTOR)
+        } else {
+            None
+        }
+    }
+    /// Runs the IRQ or NMI sequence, jumping through `vector`
+    fn service_interrupt(&mut self, vector: u16) {
+        // The opcode fetch is discarded, and the PC isn't incremented
+        self.read_byte(self.pc);
+        self.read_byte(self.pc);
+        self.interrupt(self.pc, vector, false);
+        // The handler's first instruction always runs before another interrupt is taken
+        self.irq_disabled_at_poll = true;
+    }
+    /// Total number of cycles run since power on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+    /// Reads a byte over the bus, taking one cycle
+    pub fn read_byte(&mut self, addr: u16) -> u8 {
+        self.cycles += 1;
+        if let Some(replay) = &mut self.replay {
+            match replay.next_access(addr, BusAccess::Read) {
+                ReplayedAccess::Replayed(cycle) => return cycle.data,
+                // The result is never used, as the instruction is re-run next cycle
+                ReplayedAccess::Suppressed => return 0,
+                ReplayedAccess::Live => {
+                    let data = self.memory.read(addr);
+                    replay.record(BusCycle {
+                        address: addr,
+                        data,
+                        access: BusAccess::Read,
+                    });
+                    return data;
+                }
+            }
+        }
+        self.memory.read(addr)
+    }
+    /// Writes a byte over the bus, taking one cycle
+    pub fn write_byte(&mut self, addr: u16, byte: u8) {
+        self.cycles += 1;
+        if let Some(replay) = &mut self.replay {
+            if replay.next_access(addr, BusAccess::Write) != ReplayedAccess::Live {
+                return;
+            }
+            replay.record(BusCycle {
+                address: addr,
+                data: byte,
+                access: BusAccess::Write,
+            });
+        }
+        if self.strict_rom && self.rom_write.is_none() && self.memory.is_read_only(addr) {
+            self.rom_write = Some((addr, byte));
+        }
+        self.record_write(addr);
+        self.memory.write(addr, byte);
+    }
+    /// Whether an instruction has been partly run by [`Processor::step_cycle`]
+    pub fn is_mid_instruction(&self) -> bool {
+        self.cycle_step.is_some()
+    }
+    /// Advances by a single clock cycle, returning the bus access made on it.
+    ///
+    /// Memory sees each read and write on the exact cycle the 6502 would make it, but the
+    /// registers only change once the final cycle of an instruction has run
+    pub fn step_cycle(&mut self) -> BusCycle {
+        let step = match self.cycle_step.take() {
+            Some(step) => step,
+            None => {
+                self.begin_undo_record();
+                self.rom_write = None;
+                let interrupt = self.poll_interrupts();
+                CycleStep {
+                    a: self.a,
+                    x: self.x,
+                    y: self.y,
+                    p: *self.p.raw(),
+                    s: self.s,
+                    pc: self.pc,
+                    irq_disabled_at_poll: self.irq_disabled_at_poll,
+                    cycles: self.cycles,
+                    jammed: self.jammed,
+                    waiting: self.waiting,
+                    interrupt,
+                    completed: Vec::new(),
+                }
+            }
+        };
+        self.restore_cycle_step(&step);
+        self.replay = Some(Replay::new(step.completed));
+        // Errors are left for `process_next_instruction` to report, as the state that
+        // caused them is still there
+        let _ = self.execute(step.interrupt);
+        let replay = self.replay.take().expect("replay is only taken here");
+        let cycle = replay.live.expect("every cycle accesses the bus");
+        if replay.overran {
+            // More cycles to go, so undo everything done by the suppressed part of the run
+            let step = CycleStep {
+                completed: replay.completed,
+                ..step
+            };
+            self.restore_cycle_step(&step);
+            self.cycles = step.cycles + step.completed.len() as u64;
+            self.cycle_step = Some(step);
         } else {
-            self.pc += abs as u16
+            self.finish_undo_record();
         }
+        cycle
+    }
+    fn restore_cycle_step(&mut self, step: &CycleStep) {
+        self.a = step.a;
+        self.x = step.x;
+        self.y = step.y;
+        *self.p.raw_mut() = step.p;
+        self.s = step.s;
+        self.pc = step.pc;
+        self.irq_disabled_at_poll = step.irq_disabled_at_poll;
+        self.cycles = step.cycles;
+        self.jammed = step.jammed;
+        self.waiting = step.waiting;
+    }
+    pub fn add_to_pc(&mut self, num: i8) {
+        self.pc = self.pc.wrapping_add_signed(num as i16);
     }
     pub fn push_to_stack(&mut self, byte: u8) {
-        self.memory.write_byte(self.s as u16, byte);
+        self.write_byte(0x0100 + self.s as u16, byte);
         self.s = self.s.wrapping_sub(1);
     }
     pub fn pop_from_stack(&mut self) -> u8 {
-        let byte = self.memory.read_byte(self.s as u16);
         self.s = self.s.wrapping_add(1);
-        byte
+        self.read_byte(0x0100 + self.s as u16)
+    }
+    /// Reads the stack without popping, as done while the stack pointer is being adjusted
+    fn read_stack_pointer(&mut self) {
+        self.read_byte(0x0100 + self.s as u16);
+    }
+    /// Pushes a 16-bit value onto the stack, high byte first
+    pub fn push_word_to_stack(&mut self, word: u16) {
+        let [low, high] = word.to_le_bytes();
+        self.push_to_stack(high);
+        self.push_to_stack(low);
+    }
+    /// Pops a 16-bit value from the stack, low byte first
+    pub fn pop_word_from_stack(&mut self) -> u16 {
+        let low = self.pop_from_stack();
+        let high = self.pop_from_stack();
+        u16::from_le_bytes([low, high])
     }
-    pub fn peek_byte_at_pc(&self) -> u8 {
-        self.memory.read_byte(self.pc)
+    /// Reads a little-endian 16-bit value
+    pub fn read_word(&mut self, addr: u16) -> u16 {
+        let low = self.read_byte(addr);
+        let high = self.read_byte(addr.wrapping_add(1));
+        u16::from_le_bytes([low, high])
+    }
+    /// The byte at the PC, if the bus can be read without side effects
+    pub fn peek_byte_at_pc(&self) -> Option<u8> {
+        self.memory.peek(self.pc)
     }
     pub fn take_byte_at_pc(&mut self) -> u8 {
-        let data = self.peek_byte_at_pc();
-        self.pc += 1;
+        let data = self.read_byte(self.pc);
+        self.pc = self.pc.wrapping_add(1);
         data
     }
+    /// Reads a little-endian 16-bit operand and moves the PC past it
+    pub fn take_word_at_pc(&mut self) -> u16 {
+        let low = self.take_byte_at_pc();
+        let high = self.take_byte_at_pc();
+        u16::from_le_bytes([low, high])
+    }
+    /// Reads a pointer from the zero page. The high byte wraps around to $00 rather than
+    /// leaving the zero page
+    pub fn read_zero_page_word(&mut self, pointer: u8) -> u16 {
+        let low = self.read_byte(pointer as u16);
+        let high = self.read_byte(pointer.wrapping_add(1) as u16);
+        u16::from_le_bytes([low, high])
+    }
     pub fn update_zero_and_negative_flags(&mut self, value: u8) {
-        if value == 0 {
-            self.p.set_zero_flag();
+        self.p.update(StatusFlag::Zero, value == 0);
+        self.p
+            .update(StatusFlag::Negative, (value & 0b10000000) != 0);
+    }
+    /// Whether ADC and SBC should work in decimal, which needs both the D flag and a variant
+    /// which supports it
+    fn decimal_mode(&self) -> bool {
+        self.p.get(StatusFlag::Decimal) && self.variant.has_decimal_mode()
+    }
+    /// Adds `data` and the carry flag to the accumulator, as done by ADC
+    fn add_with_carry(&mut self, data: u8) {
+        if self.decimal_mode() {
+            let result = if self.variant.is_cmos() {
+                decimal::cmos_add(self.a, data, self.p.get(StatusFlag::Carry))
+            } else {
+                decimal::add(self.a, data, self.p.get(StatusFlag::Carry))
+            };
+            self.apply_decimal_result(result);
+        } else {
+            self.binary_add_with_carry(data);
+        }
+    }
+    /// Subtracts `data` and the inverted carry flag from the accumulator, as done by SBC
+    fn subtract_with_borrow(&mut self, data: u8) {
+        if self.decimal_mode() {
+            let result = if self.variant.is_cmos() {
+                decimal::cmos_subtract(self.a, data, self.p.get(StatusFlag::Carry))
+            } else {
+                decimal::subtract(self.a, data, self.p.get(StatusFlag::Carry))
+            };
+            self.apply_decimal_result(result);
+        } else {
+            // Binary subtraction is addition of the inverted operand
+            self.binary_add_with_carry(!data);
+        }
+    }
+    fn apply_decimal_result(&mut self, result: DecimalResult) {
+        // The 65C02 spends an extra cycle getting valid flags
+        if self.variant.is_cmos() {
+            self.read_byte(self.pc);
+        }
+        self.a = result.value;
+        self.p.update(StatusFlag::Carry, result.carry);
+        self.p.update(StatusFlag::Overflow, result.overflow);
+        // The NMOS 6502 doesn't set N and Z from the adjusted result
+        self.p.update(StatusFlag::Zero, result.zero);
+        self.p.update(StatusFlag::Negative, result.negative);
+    }
+    fn binary_add_with_carry(&mut self, data: u8) {
+        let sum = self.a as u16 + data as u16 + self.p.get(StatusFlag::Carry) as u16;
+        let res = sum as u8;
+        // Signed overflow happens when both inputs share a sign which differs from the result's
+        self.p.update(
+            StatusFlag::Overflow,
+            (!(self.a ^ data) & (self.a ^ res) & 0b10000000) != 0,
+        );
+        self.p.update(StatusFlag::Carry, sum > 0xFF);
+        self.a = res;
+        self.update_zero_and_negative_flags(res);
+    }
+    /// Sets the flags as CMP, CPX and CPY do
+    fn compare(&mut self, register: u8, data: u8) {
+        self.p.update(StatusFlag::Carry, register >= data);
+        self.update_zero_and_negative_flags(register.wrapping_sub(data));
+    }
+    /// Performs a read-modify-write instruction on its operand, which may be the accumulator.
+    /// In memory, the NMOS 6502 writes back the unmodified value before the result, while the
+    /// 65C02 reads it a second time instead
+    fn modify_operand(
+        &mut self,
+        addr: u16,
+        addressing_mode: AddressingMode,
+        modify: impl FnOnce(&mut Self, u8) -> u8,
+    ) -> u8 {
+        if addressing_mode == AddressingMode::Accumulator {
+            self.a = modify(self, self.a);
+            self.a
         } else {
-            self.p.clear_zero_flag();
+            let data = self.read_byte(addr);
+            if self.variant.is_cmos() {
+                self.read_byte(addr);
+            } else {
+                self.write_byte(addr, data);
+            }
+            let result = modify(self, data);
+            self.write_byte(addr, result);
+            result
         }
-        if (value & 0b10000000) != 0 {
-            self.p.set_negative_flag();
+    }
+    fn shift_left(&mut self, data: u8) -> u8 {
+        // Bit 7 is shifted into the carry
+        self.p.update(StatusFlag::Carry, (data & 0b10000000) != 0);
+        let result = data << 1;
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+    fn rotate_left(&mut self, data: u8) -> u8 {
+        // Old carry goes into bit 0, and bit 7 becomes the new carry
+        let result = (data << 1) | self.p.get(StatusFlag::Carry) as u8;
+        self.p.update(StatusFlag::Carry, (data & 0b10000000) != 0);
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+    fn shift_right(&mut self, data: u8) -> u8 {
+        // Bit 0 is shifted into the carry
+        self.p.update(StatusFlag::Carry, (data & 0b00000001) != 0);
+        let result = data >> 1;
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+    fn rotate_right(&mut self, data: u8) -> u8 {
+        // Old carry goes into bit 7, and bit 0 becomes the new carry
+        let result = (data >> 1) | ((self.p.get(StatusFlag::Carry) as u8) << 7);
+        self.p.update(StatusFlag::Carry, (data & 0b00000001) != 0);
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+    fn increment(&mut self, data: u8) -> u8 {
+        let result = data.wrapping_add(1);
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+    fn decrement(&mut self, data: u8) -> u8 {
+        let result = data.wrapping_sub(1);
+        self.update_zero_and_negative_flags(result);
+        result
+    }
+    /// ARR ANDs the accumulator with its operand and rotates it right, but sets C and V from
+    /// the adder used for the rotation. In decimal mode, the result is also BCD corrected
+    /// (see http://www.zimmers.net/anonftp/pub/cbm/documents/chipdata/64doc)
+    fn and_rotate_right(&mut self, data: u8) {
+        let and = self.a & data;
+        let carry = self.p.get(StatusFlag::Carry);
+        let mut result = (and >> 1) | ((carry as u8) << 7);
+        if self.decimal_mode() {
+            self.update_zero_and_negative_flags(result);
+            // N comes from the carry in, as that's what was rotated into bit 7
+            self.p
+                .update(StatusFlag::Overflow, ((and ^ result) & 0b01000000) != 0);
+            let (high, low) = (and >> 4, and & 0x0F);
+            if low + (low & 0x01) > 5 {
+                result = (result & 0xF0) | (result.wrapping_add(6) & 0x0F);
+            }
+            let high_carry = high + (high & 0x01) > 5;
+            if high_carry {
+                result = result.wrapping_add(0x60);
+            }
+            self.p.update(StatusFlag::Carry, high_carry);
         } else {
-            self.p.clear_negative_flag();
+            self.update_zero_and_negative_flags(result);
+            self.p.update(StatusFlag::Carry, (result & 0b01000000) != 0);
+            self.p.update(
+                StatusFlag::Overflow,
+                (((result >> 6) ^ (result >> 5)) & 0b1) != 0,
+            );
         }
+        self.a = result;
     }
-    /// Fetches the "destination" for the instruction.
-    /// It is returned as a mutable reference to where the data is located (not yet but planned)
-    pub fn fetch_address(&mut self, addressing_mode: Option<AddressingMode>) -> u16 {
-        let addressing_mode = if let Some(a) = addressing_mode {
-            a
+    /// SHA, SHX, SHY and TAS store a register ANDed with the high byte of the unindexed address
+    /// plus one. If indexing crossed a page, the stored value also replaces the high byte of
+    /// the address that is written to
+    fn store_and_high_byte(&mut self, addr: u16, addressing_mode: AddressingMode, value: u8) {
+        let index = match addressing_mode {
+            AddressingMode::AbsoluteX => self.x,
+            _ => self.y,
+        };
+        let base = addr.wrapping_sub(index as u16);
+        let value = value & ((base >> 8) as u8).wrapping_add(1);
+        let addr = if (base & 0xFF00) != (addr & 0xFF00) {
+            ((value as u16) << 8) | (addr & 0x00FF)
         } else {
-            return u16::MAX;
+            addr
         };
+        self.write_byte(addr, value);
+    }
+    fn branch_if(&mut self, condition: bool, addr: u16) {
+        if condition {
+            // Taken branches spend a cycle adding the offset, and another if the page changes
+            self.read_byte(self.pc);
+            if (self.pc & 0xFF00) != (addr & 0xFF00) {
+                self.read_byte((self.pc & 0xFF00) | (addr & 0x00FF));
+            }
+            self.pc = addr;
+        }
+    }
+    /// Adds `index` to `base`. The 6502 adds to the low byte first, so it reads from the
+    /// address without the carry applied before the correct one. That read is skipped if
+    /// there was no carry and the instruction only reads its operand.
+    /// The 65C02 re-reads the last operand byte instead, to avoid touching I/O by accident
+    fn index_address(&mut self, base: u16, index: u8, access: OperandAccess) -> u16 {
+        let addr = base.wrapping_add(index as u16);
+        let page_crossed = (base & 0xFF00) != (addr & 0xFF00);
+        if page_crossed || access != OperandAccess::Read {
+            if self.variant.is_cmos() {
+                self.read_byte(self.pc.wrapping_sub(1));
+            } else {
+                self.read_byte((base & 0xFF00) | (addr & 0x00FF));
+            }
+        }
+        addr
+    }
+    /// Fetches the "destination" for the instruction, consuming its operand bytes.
+    /// Afterwards the PC points at the next instruction
+    pub fn fetch_address(&mut self, addressing_mode: AddressingMode, access: OperandAccess) -> u16 {
         match addressing_mode {
             AddressingMode::Immediate => {
                 // PC is already at byte immediate mode needs
-                self.pc
+                let addr = self.pc;
+                self.pc = self.pc.wrapping_add(1);
+                addr
             }
-            AddressingMode::ZeroPage => self.peek_byte_at_pc() as u16,
-            AddressingMode::ZeroPageX => self.peek_byte_at_pc() as u16 + self.x as u16,
-            AddressingMode::ZeroPageY => self.peek_byte_at_pc() as u16 + self.y as u16,
-            AddressingMode::Absolute => {
-                // Read two bytes
-                let byte1 = self.peek_byte_at_pc() as u16;
-                self.pc += 1;
-                let byte2 = self.peek_byte_at_pc() as u16;
-
-                byte1 + (byte2 << 8)
+            // BBR and BBS fetch their branch offset themselves
+            AddressingMode::ZeroPage | AddressingMode::ZeroPageRelative => {
+                self.take_byte_at_pc() as u16
+            }
+            // Indexing never leaves the zero page. The unindexed address is read while adding
+            AddressingMode::ZeroPageX => {
+                let base = self.take_byte_at_pc();
+                self.read_byte(base as u16);
+                base.wrapping_add(self.x) as u16
             }
+            AddressingMode::ZeroPageY => {
+                let base = self.take_byte_at_pc();
+                self.read_byte(base as u16);
+                base.wrapping_add(self.y) as u16
+            }
+            AddressingMode::Absolute => self.take_word_at_pc(),
             AddressingMode::Relative => {
-                let offset = self.take_byte_at_pc() as i8 as u16;
-                self.pc.wrapping_add(offset)
+                let offset = self.take_byte_at_pc() as i8;
+                self.pc.wrapping_add_signed(offset as i16)
+            }
+            AddressingMode::Indirect => {
+                let pointer = self.take_word_at_pc();
+                if !self.variant.has_indirect_jump_bug() {
+                    // Fixed on the 65C02, at the cost of a cycle
+                    self.read_byte(self.pc.wrapping_sub(1));
+                    return self.read_word(pointer);
+                }
+                // The high byte is fetched without carrying into the pointer's high byte,
+                // so JMP ($xxFF) reads it from $xx00
+                let high_pointer = (pointer & 0xFF00) | (pointer as u8).wrapping_add(1) as u16;
+                let low = self.read_byte(pointer);
+                let high = self.read_byte(high_pointer);
+                u16::from_le_bytes([low, high])
+            }
+            AddressingMode::AbsoluteX => {
+                let base = self.take_word_at_pc();
+                self.index_address(base, self.x, access)
+            }
+            AddressingMode::AbsoluteY => {
+                let base = self.take_word_at_pc();
+                self.index_address(base, self.y, access)
+            }
+            AddressingMode::IndirectX => {
+                let base = self.take_byte_at_pc();
+                self.read_byte(base as u16);
+                self.read_zero_page_word(base.wrapping_add(self.x))
+            }
+            AddressingMode::IndirectY => {
+                let pointer = self.take_byte_at_pc();
+                let base = self.read_zero_page_word(pointer);
+                self.index_address(base, self.y, access)
+            }
+            AddressingMode::ZeroPageIndirect => {
+                let pointer = self.take_byte_at_pc();
+                self.read_zero_page_word(pointer)
             }
-            AddressingMode::Implicit => {
-                // Will panic if this access is attempted
+            AddressingMode::AbsoluteIndexedIndirect => {
+                let base = self.take_word_at_pc();
+                self.read_byte(self.pc.wrapping_sub(1));
+                self.read_word(base.wrapping_add(self.x as u16))
+            }
+            AddressingMode::Implicit | AddressingMode::Accumulator => {
+                // Single byte instructions still read the following byte
+                self.read_byte(self.pc);
                 u16::MAX
             }
-            AddressingMode::Accumulator => u16::MAX,
-            _ => {
-                unimplemented!("Addressing mode {:?} isn't implemented", addressing_mode)
+        }
+    }
+    /// Makes the run methods stop before running the instruction at `addr`
+    pub fn add_breakpoint(&mut self, addr: u16) {
+        self.breakpoints.insert(addr);
+    }
+    /// Returns whether there was a breakpoint at `addr`
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr)
+    }
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+    /// Runs `count` instructions (interrupt sequences count as one)
+    pub fn step(&mut self, count: usize) -> StopReason {
+        let mut remaining = count;
+        self.run(|_| {
+            if remaining == 0 {
+                return true;
+            }
+            remaining -= 1;
+            false
+        })
+        .unwrap_or(StopReason::StepsCompleted)
+    }
+    /// Runs whole instructions until at least `cycles` cycles have passed. The last instruction
+    /// may go over the budget
+    pub fn run_for_cycles(&mut self, cycles: u64) -> StopReason {
+        let end = self.cycles + cycles;
+        self.run(|processor| processor.cycles >= end)
+            .unwrap_or(StopReason::CyclesElapsed)
+    }
+    /// Runs until `predicate` returns `true`. It's checked before each instruction, including
+    /// the first
+    pub fn run_until(&mut self, mut predicate: impl FnMut(&Self) -> bool) -> StopReason {
+        self.run(|processor| predicate(processor))
+            .unwrap_or(StopReason::Predicate)
+    }
+    /// Runs until the next instruction is a BRK, which test programs use to mark their end
+    pub fn run_until_brk(&mut self) -> StopReason {
+        self.run(|processor| processor.peek_byte_at_pc() == Some(0))
+            .unwrap_or(StopReason::Brk)
+    }
+    /// Runs instructions until `should_stop` returns `true`, giving `None`, or until stopped by
+    /// a breakpoint or error. Breakpoints are ignored on the first instruction, so that a run
+    /// can carry on from one
+    fn run(&mut self, mut should_stop: impl FnMut(&Self) -> bool) -> Option<StopReason> {
+        let mut first = true;
+        loop {
+            if !first && self.breakpoints.contains(&self.pc) {
+                return Some(StopReason::Breakpoint(self.pc));
+            }
+            if should_stop(self) {
+                return None;
+            }
+            if let Err(error) = self.process_next_instruction() {
+                return Some(StopReason::Halted(error));
+            }
+            first = false;
+        }
+    }
+    /// Executes the next instruction, or the interrupt sequence if an interrupt is pending.
+    /// Returns the number of cycles taken.
+    /// If an instruction has been partly run by [`Processor::step_cycle`], only its remaining
+    /// cycles are run.
+    ///
+    /// Returns an error if the instruction couldn't be run, or if the processor is jammed.
+    /// A jammed processor still spends a cycle on each call, and keeps failing until it's reset
+    pub fn process_next_instruction(&mut self) -> Result<u8, CpuError> {
+        let start_cycles = self.cycles;
+        let start_pc = self.cycle_step.as_ref().map_or(self.pc, |step| step.pc);
+        if self.cycle_step.is_some() {
+            while self.cycle_step.is_some() {
+                self.step_cycle();
+            }
+        } else {
+            self.begin_undo_record();
+            self.rom_write = None;
+            let interrupt = self.poll_interrupts();
+            let result = self.execute(interrupt);
+            self.finish_undo_record();
+            result?;
+        }
+        if let Some((address, value)) = self.rom_write.take() {
+            return Err(CpuError::RomWrite {
+                pc: start_pc,
+                bytes: self.instruction_bytes(start_pc),
+                address,
+                value,
+            });
+        }
+        if let Some(pc) = self.jammed {
+            return Err(CpuError::Jammed {
+                pc,
+                bytes: self.instruction_bytes(pc),
+            });
+        }
+        Ok((self.cycles - start_cycles) as u8)
+    }
+    /// Runs either the sequence for an interrupt, or the next instruction
+    fn execute(&mut self, interrupt: Option<u16>) -> Result<(), CpuError> {
+        if self.is_jammed() {
+            // The address bus is left at $FFFF
+            self.read_byte(0xFFFF);
+            return Ok(());
+        }
+        if self.waiting {
+            self.read_byte(self.pc);
+            return Ok(());
+        }
+        match interrupt {
+            Some(vector) => {
+                self.service_interrupt(vector);
+                Ok(())
+            }
+            None => self.execute_instruction(),
+        }
+    }
+    /// The opcode at `pc` and its operands, peeked without side effects. Stops short at any
+    /// byte the bus can't peek
+    fn instruction_bytes(&self, pc: u16) -> Vec<u8> {
+        let Some(opcode) = self.memory.peek(pc) else {
+            return Vec::new();
+        };
+        let length = Opcode::decode(opcode, self.variant).length;
+        (0..length as u16)
+            .map_while(|i| self.memory.peek(pc.wrapping_add(i)))
+            .collect()
+    }
+    /// Deals with an undefined opcode according to the policy, after it has been fetched
+    fn handle_undefined_opcode(&mut self, pc: u16, value: u8) -> Result<(), CpuError> {
+        let opcode = Opcode::decode(value, self.variant);
+        match self.undefined_opcode_policy {
+            UndefinedOpcodePolicy::Execute => unreachable!("undefined opcodes are run as normal"),
+            UndefinedOpcodePolicy::Halt => {
+                self.pc = pc;
+                return Err(CpuError::UnknownOpcode {
+                    pc,
+                    bytes: self.instruction_bytes(pc),
+                });
+            }
+            UndefinedOpcodePolicy::Nop => self.skip_operands(opcode),
+            UndefinedOpcodePolicy::Trap(handler) => {
+                self.skip_operands(opcode);
+                // Only call the handler once, when stepping reaches the instruction's last cycle
+                if self.is_last_pass() {
+                    handler(self, pc, value);
+                }
+            }
+            UndefinedOpcodePolicy::Jam => {
+                self.read_byte(self.pc);
+                self.jammed = Some(pc);
             }
         }
+        Ok(())
     }
-    pub fn process_next_instruction(&mut self) {
+    /// Whether the opcode was fetched on this pass, so that nothing else the instruction does
+    /// has happened yet. Only meaningful straight after the fetch
+    fn is_first_pass(&self) -> bool {
+        self.replay
+            .as_ref()
+            .is_none_or(|replay| replay.live.is_some() && replay.completed.len() == 1)
+    }
+    /// Whether the instruction being run will finish on this pass. When stepping, an
+    /// instruction is run again for each of its cycles, and only the last run finishes it
+    fn is_last_pass(&self) -> bool {
+        self.replay
+            .as_ref()
+            .is_none_or(|replay| replay.live.is_some() && !replay.overran)
+    }
+    fn skip_operands(&mut self, opcode: Opcode) {
+        for _ in 1..opcode.length {
+            self.take_byte_at_pc();
+        }
+    }
+    fn execute_instruction(&mut self) -> Result<(), CpuError> {
+        let before = self.state();
         let value = self.take_byte_at_pc();
-        let instruction = Instruction::from(value);
-        let addressing_mode = AddressingMode::try_from((value, instruction)).ok();
-        dbg!(&instruction, &addressing_mode);
-        let addr = self.fetch_address(addressing_mode);
+        if self.tracer.is_none() {
+            return self.run_instruction(before.pc, value).map(|_| ());
+        }
+        let opcode = Opcode::decode(value, self.variant);
+        let mut event = TraceEvent {
+            pc: before.pc,
+            opcode: value,
+            instruction: opcode.instruction,
+            addressing_mode: opcode.addressing_mode,
+            effective_address: None,
+            state: before,
+        };
+        if self.is_first_pass()
+            && let Some(tracer) = &mut self.tracer
+        {
+            tracer.before_instruction(&event);
+        }
+        event.effective_address = self.run_instruction(before.pc, value)?;
+        if self.is_last_pass() {
+            event.state = self.state();
+            if let Some(tracer) = &mut self.tracer {
+                tracer.after_instruction(&event);
+            }
+        }
+        Ok(())
+    }
+    /// Runs the instruction with opcode `value`, which has just been fetched from
+    /// `instruction_pc`. Returns its effective address, if it has one
+    fn run_instruction(&mut self, instruction_pc: u16, value: u8) -> Result<Option<u16>, CpuError> {
+        let irq_disabled = self.p.get(StatusFlag::InterruptDisable);
+        let opcode = Opcode::decode(value, self.variant);
+        let instruction = opcode.instruction;
+        let addressing_mode = opcode.addressing_mode;
+        if !instruction.is_defined()
+            && !matches!(self.undefined_opcode_policy, UndefinedOpcodePolicy::Execute)
+        {
+            self.handle_undefined_opcode(instruction_pc, value)?;
+            self.irq_disabled_at_poll = self.p.get(StatusFlag::InterruptDisable);
+            return Ok(None);
+        }
+        if !self.variant.is_cmos()
+            && matches!(
+                addressing_mode,
+                AddressingMode::ZeroPageIndirect
+                    | AddressingMode::AbsoluteIndexedIndirect
+                    | AddressingMode::ZeroPageRelative
+            )
+        {
+            // Only the 65C02 can address its operands like this. Leave the PC on the opcode
+            self.pc = instruction_pc;
+            return Err(CpuError::UnimplementedMode {
+                pc: instruction_pc,
+                bytes: self.instruction_bytes(instruction_pc),
+                addressing_mode,
+            });
+        }
+        let mut access = instruction.operand_access();
+        if self.variant.is_cmos()
+            && matches!(
+                instruction,
+                Instruction::GroupTwo(
+                    Group2Instruction::ASL
+                        | Group2Instruction::ROL
+                        | Group2Instruction::LSR
+                        | Group2Instruction::ROR
+                )
+            )
+        {
+            // The 65C02 only spends the extra indexing cycle on shifts when crossing a page
+            access = OperandAccess::Read;
+        }
+        let addr = match (instruction, addressing_mode) {
+            // JSR fetches the high byte of its target after pushing the return address
+            (Instruction::SpecialCase(SpecialCase::JSRABS), _) => u16::MAX,
+            // The 65C02's single byte NOPs only take one cycle
+            (Instruction::Cmos(CmosInstruction::NOP), AddressingMode::Implicit) => u16::MAX,
+            _ => self.fetch_address(addressing_mode, access),
+        };
 
         match instruction {
-            Instruction::GroupOne(instruction) => {
-                match instruction {
-                    Group1Instruction::ORA => self.a |= self.memory.read_byte(addr),
-                    Group1Instruction::AND => self.a &= self.memory.read_byte(addr),
-                    Group1Instruction::EOR => self.a ^= self.memory.read_byte(addr),
-                    Group1Instruction::ADC => {
-                        let data = self.memory.read_byte(addr);
-                        let bit7_initial = (data & 0b10000000) != 0;
-                        let (res1, overflowed_1) = self.a.overflowing_add(data);
-                        // Add carry flag and check for overflow again
-                        // Add separately, as data + 1 could overflow too
-                        let (res, overflowed_2) =
-                            res1.overflowing_add(if self.p.get_carry_flag() { 1 } else { 0 });
-                        let overflowed = overflowed_1 | overflowed_2;
-
-                        let bit7_result = (res & 0b10000000) != 0;
-                        // If the result and initial seventh bits aren't the same, then a signed overflow has occured
-                        if bit7_initial != bit7_result {
-                            self.p.set_overflow_flag();
-                        } else {
-                            self.p.clear_overflow_flag();
-                        }
-                        if overflowed {
-                            self.p.set_carry_flag();
-                        } else {
-                            self.p.clear_carry_flag();
-                        }
-                        // Negative value if this is true
-                        if bit7_result {
-                            self.p.set_negative_flag();
-                        } else {
-                            self.p.clear_negative_flag();
-                        }
-                        self.a = res;
-                    }
-                    Group1Instruction::STA => self.memory.write_byte(addr, self.a),
-                    Group1Instruction::LDA => {
-                        // Load data into accumulator
-                        self.a = self.memory.read_byte(addr)
-                    }
-                    Group1Instruction::CMP => {
-                        let res = self.a.wrapping_sub(self.memory.read_byte(addr));
-                        let is_negative = (res & 0b10000000) != 0;
-                        if is_negative {
-                            self.p.set_negative_flag();
-                        } else {
-                            self.p.clear_negative_flag();
-                        }
-                        if res == 0 {
-                            self.p.set_zero_flag();
-                        } else {
-                            self.p.clear_zero_flag();
-                        }
-                        if !is_negative && res != 0 {
-                            self.p.set_carry_flag();
-                        } else {
-                            self.p.clear_carry_flag();
-                        }
-                    }
-                    Group1Instruction::SBC => todo!(),
+            Instruction::GroupOne(instruction) => match instruction {
+                Group1Instruction::ORA => {
+                    self.a |= self.read_byte(addr);
+                    self.update_zero_and_negative_flags(self.a);
                 }
-                self.pc += 1;
-            }
-            Instruction::GroupTwo(instruction) => {
-                match instruction {
-                    Group2Instruction::ASL => {
-                        let data = self.memory.read_byte(addr);
-                        // Get bit 7
-                        let bit7 = (data & 0b10000000) != 0;
-                        if bit7 {
-                            self.p.set_carry_flag();
-                        } else {
-                            self.p.clear_carry_flag();
-                        }
-                        // Write the result, but with bit 0 set to 0
-                        let result = (data << 1) & 0b11111110;
-                        // Get bit 7 of the result
-                        let bit7 = (data & 0b10000000) != 0;
-                        if bit7 {
-                            self.p.set_negative_flag();
-                        } else {
-                            self.p.clear_negative_flag();
-                        }
-                        self.memory.write_byte(addr, result);
-                    }
-                    Group2Instruction::ROL => todo!(),
-                    Group2Instruction::LSR => todo!(),
-                    Group2Instruction::ROR => todo!(),
-                    Group2Instruction::STX => self.memory.write_byte(addr, self.x),
-                    Group2Instruction::LDX => self.x = self.memory.read_byte(addr),
-                    Group2Instruction::DEC => {
-                        self.a = self.a.wrapping_sub(1);
-                        self.update_zero_and_negative_flags(self.a);
-                    }
-                    Group2Instruction::INC => {
-                        let byte = self.memory.read_byte(addr) + 1;
-                        self.memory.write_byte(addr, byte);
-                        self.update_zero_and_negative_flags(byte);
-                    }
+                Group1Instruction::AND => {
+                    self.a &= self.read_byte(addr);
+                    self.update_zero_and_negative_flags(self.a);
                 }
-                self.pc += 1;
-            }
-            Instruction::GroupThree(instruction) => {
-                match instruction {
-                    Group3Instruction::BIT => {
-                        let byte = self.memory.read_byte(addr);
-                        if (byte & self.a) == 0 {
-                            self.p.set_zero_flag();
-                        } else {
-                            self.p.clear_zero_flag();
-                        }
-                        let bit7 = byte & 0b10000000;
-                        let bit6 = byte & 0b01000000;
-                        if bit7 == 0 {
-                            self.p.clear_negative_flag();
-                        } else {
-                            self.p.set_negative_flag();
-                        }
-                        if bit6 == 0 {
-                            self.p.clear_overflow_flag();
-                        } else {
-                            self.p.set_overflow_flag();
-                        }
-                    }
-                    Group3Instruction::JMP => {
-                        // Kinda hacky, so increment at the end of this `match` sets PC to addr
-                        self.pc = addr - 1
-                    }
-                    Group3Instruction::STY => self.memory.write_byte(addr, self.y),
-                    Group3Instruction::LDY => self.y = self.memory.read_byte(addr),
-                    Group3Instruction::CPY => todo!(),
-                    Group3Instruction::CPX => {
-                        let res = self.x.wrapping_sub(self.memory.read_byte(addr));
-                        let is_negative = (res & 0b10000000) != 0;
-                        if is_negative {
-                            self.p.set_negative_flag();
-                        } else {
-                            self.p.clear_negative_flag();
-                        }
-                        if res == 0 {
-                            self.p.set_zero_flag();
-                        } else {
-                            self.p.clear_zero_flag();
-                        }
-                        if !is_negative && res != 0 {
-                            self.p.set_carry_flag();
-                        } else {
-                            self.p.clear_carry_flag();
-                        }
+                Group1Instruction::EOR => {
+                    self.a ^= self.read_byte(addr);
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                Group1Instruction::ADC => {
+                    let data = self.read_byte(addr);
+                    self.add_with_carry(data);
+                }
+                Group1Instruction::STA => self.write_byte(addr, self.a),
+                Group1Instruction::LDA => {
+                    // Load data into accumulator
+                    self.a = self.read_byte(addr);
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                Group1Instruction::CMP => {
+                    let data = self.read_byte(addr);
+                    self.compare(self.a, data);
+                }
+                Group1Instruction::SBC => {
+                    let data = self.read_byte(addr);
+                    self.subtract_with_borrow(data);
+                }
+            },
+            Instruction::GroupTwo(instruction) => match instruction {
+                Group2Instruction::ASL => {
+                    self.modify_operand(addr, addressing_mode, Self::shift_left);
+                }
+                Group2Instruction::ROL => {
+                    self.modify_operand(addr, addressing_mode, Self::rotate_left);
+                }
+                Group2Instruction::LSR => {
+                    self.modify_operand(addr, addressing_mode, Self::shift_right);
+                }
+                Group2Instruction::ROR => {
+                    self.modify_operand(addr, addressing_mode, Self::rotate_right);
+                }
+                Group2Instruction::STX => self.write_byte(addr, self.x),
+                Group2Instruction::LDX => {
+                    self.x = self.read_byte(addr);
+                    self.update_zero_and_negative_flags(self.x);
+                }
+                Group2Instruction::DEC => {
+                    self.modify_operand(addr, addressing_mode, Self::decrement);
+                }
+                Group2Instruction::INC => {
+                    self.modify_operand(addr, addressing_mode, Self::increment);
+                }
+            },
+            Instruction::GroupThree(instruction) => match instruction {
+                Group3Instruction::BIT => {
+                    let byte = self.read_byte(addr);
+                    self.p.update(StatusFlag::Zero, (byte & self.a) == 0);
+                    // BIT #$xx on the 65C02 only affects Z
+                    if addressing_mode != AddressingMode::Immediate {
+                        self.p
+                            .update(StatusFlag::Negative, (byte & 0b10000000) != 0);
+                        self.p
+                            .update(StatusFlag::Overflow, (byte & 0b01000000) != 0);
                     }
                 }
-                self.pc += 1;
-            }
+                Group3Instruction::JMP => self.pc = addr,
+                Group3Instruction::STY => self.write_byte(addr, self.y),
+                Group3Instruction::LDY => {
+                    self.y = self.read_byte(addr);
+                    self.update_zero_and_negative_flags(self.y);
+                }
+                Group3Instruction::CPY => {
+                    let data = self.read_byte(addr);
+                    self.compare(self.y, data);
+                }
+                Group3Instruction::CPX => {
+                    let data = self.read_byte(addr);
+                    self.compare(self.x, data);
+                }
+            },
             Instruction::ConditionalBranch(instruction) => {
+                // `addr` is the branch target, and the PC already points at the next instruction
                 match instruction {
                     ConditionalBranchInstruction::BPL => {
-                        if !self.p.get_negative_flag() {
-                            self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(!self.p.get(StatusFlag::Negative), addr)
                     }
                     ConditionalBranchInstruction::BMI => {
-                        if self.p.get_negative_flag() {
-                            self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(self.p.get(StatusFlag::Negative), addr)
                     }
                     ConditionalBranchInstruction::BVC => {
-                        if !self.p.get_overflow_flag() {
-                            self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(!self.p.get(StatusFlag::Overflow), addr)
                     }
                     ConditionalBranchInstruction::BVS => {
-                        if self.p.get_overflow_flag() {
-                            self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(self.p.get(StatusFlag::Overflow), addr)
                     }
                     ConditionalBranchInstruction::BCC => {
-                        if !self.p.get_carry_flag() {
-                            self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(!self.p.get(StatusFlag::Carry), addr)
                     }
                     ConditionalBranchInstruction::BCS => {
-                        if self.p.get_carry_flag() {
-                            self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(self.p.get(StatusFlag::Carry), addr)
                     }
                     ConditionalBranchInstruction::BNE => {
-                        if !self.p.get_zero_flag() {
-                            dbg!("Branched!");
-                            self.pc = addr;
-                            // self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(!self.p.get(StatusFlag::Zero), addr)
                     }
                     ConditionalBranchInstruction::BEQ => {
-                        if self.p.get_zero_flag() {
-                            self.add_to_pc(interpret_as_signed(self.memory.read_byte(addr)));
-                        }
+                        self.branch_if(self.p.get(StatusFlag::Zero), addr)
                     }
                 }
-                // self.pc += 2;
-                println!("0x{:X}", &self.pc);
             }
             Instruction::SingleByte(instruction) => match instruction {
                 // Stack Operations
-                // Push processor status onto stack
-                SingleByteInstruction::PHP => self.push_to_stack(*self.p.raw()),
+                // Push processor status onto stack, with the break and unused bits set
+                SingleByteInstruction::PHP => self.push_to_stack(self.p.to_pushed(true)),
                 // Pull processor status from stack
-                SingleByteInstruction::PLP => *self.p.raw_mut() = self.pop_from_stack(),
+                SingleByteInstruction::PLP => {
+                    self.read_stack_pointer();
+                    let pulled = self.pop_from_stack();
+                    self.p.set_pulled(pulled);
+                }
                 // Push accumulator onto stack
                 SingleByteInstruction::PHA => self.push_to_stack(self.a),
                 // Pull accumulator from stack
-                SingleByteInstruction::PLA => self.a = self.pop_from_stack(),
+                SingleByteInstruction::PLA => {
+                    self.read_stack_pointer();
+                    self.a = self.pop_from_stack();
+                    self.update_zero_and_negative_flags(self.a);
+                }
                 // Transfer X to stack pointer
                 SingleByteInstruction::TXS => self.s = self.x,
                 // Transfer stack pointer to X
-                SingleByteInstruction::TSX => self.x = self.s,
+                SingleByteInstruction::TSX => {
+                    self.x = self.s;
+                    self.update_zero_and_negative_flags(self.x);
+                }
 
                 SingleByteInstruction::BRK => {
-                    let [byte1, byte2] = (self.pc + 2).to_le_bytes();
-                    self.push_to_stack(byte1);
-                    self.push_to_stack(byte2);
-                    // Set break command flag only for pushing to stack
-                    self.p.set_break_command_flag();
-                    self.push_to_stack(*self.p.raw());
-                    self.p.clear_break_command_flag();
-                    self.pc = 0xFFFE;
+                    // BRK is followed by a padding byte, which is skipped on return
+                    self.interrupt(self.pc.wrapping_add(1), IRQ_VECTOR, true)
                 }
                 SingleByteInstruction::RTI => {
-                    let flags = self.pop_from_stack();
-                    *self.p.raw_mut() = flags;
-                    // Pushed flags had break command, which shouldn't be restored, so it's cleared here
-                    self.p.clear_break_command_flag();
-                    // Pull PC
-                    let byte1 = self.pop_from_stack();
-                    let byte2 = self.pop_from_stack();
-                    self.pc = ((byte1 as u16) << 8) + byte2 as u16
+                    self.read_stack_pointer();
+                    let pulled = self.pop_from_stack();
+                    self.p.set_pulled(pulled);
+                    self.pc = self.pop_word_from_stack();
                 }
                 SingleByteInstruction::RTS => {
-                    let byte1 = self.pop_from_stack();
-                    let byte2 = self.pop_from_stack();
-                    self.pc = ((byte1 as u16) << 8) + byte2 as u16 + 1
+                    self.read_stack_pointer();
+                    // JSR pushed the address of its last byte, which is read again while incrementing
+                    self.pc = self.pop_word_from_stack();
+                    self.take_byte_at_pc();
                 }
                 SingleByteInstruction::DEY => {
                     self.y = self.y.wrapping_sub(1);
@@ -379,17 +1246,17 @@ impl Processor {
                     self.x = self.x.wrapping_add(1);
                     self.update_zero_and_negative_flags(self.x);
                 }
-                SingleByteInstruction::CLC => self.p.clear_carry_flag(),
-                SingleByteInstruction::SEC => self.p.set_carry_flag(),
-                SingleByteInstruction::CLI => self.p.clear_interrupt_disable_flag(),
-                SingleByteInstruction::SEI => self.p.set_interrupt_disable_flag(),
+                SingleByteInstruction::CLC => self.p.clear(StatusFlag::Carry),
+                SingleByteInstruction::SEC => self.p.set(StatusFlag::Carry),
+                SingleByteInstruction::CLI => self.p.clear(StatusFlag::InterruptDisable),
+                SingleByteInstruction::SEI => self.p.set(StatusFlag::InterruptDisable),
                 SingleByteInstruction::TYA => {
                     self.a = self.y;
                     self.update_zero_and_negative_flags(self.a);
                 }
-                SingleByteInstruction::CLV => self.p.clear_overflow_flag(),
-                SingleByteInstruction::CLD => self.p.clear_decimal_mode_flag(),
-                SingleByteInstruction::SED => self.p.set_decimal_mode_flag(),
+                SingleByteInstruction::CLV => self.p.clear(StatusFlag::Overflow),
+                SingleByteInstruction::CLD => self.p.clear(StatusFlag::Decimal),
+                SingleByteInstruction::SED => self.p.set(StatusFlag::Decimal),
                 SingleByteInstruction::TXA => {
                     self.a = self.x;
                     self.update_zero_and_negative_flags(self.a);
@@ -401,58 +1268,2201 @@ impl Processor {
                 }
 
                 SingleByteInstruction::DEX => {
-                    self.x -= 1;
+                    self.x = self.x.wrapping_sub(1);
                     self.update_zero_and_negative_flags(self.x);
                 }
-                SingleByteInstruction::NOP => self.pc += 1,
+                SingleByteInstruction::NOP => {}
             },
             Instruction::SpecialCase(instruction) => match instruction {
                 SpecialCase::JSRABS => {
-                    // PC is already address (minus one) of return, as PC has been incremented by parsing address that JSR is acting on
-                    let [byte1, byte2] = self.pc.to_le_bytes();
-                    self.push_to_stack(byte1);
-                    self.push_to_stack(byte2);
+                    let low = self.take_byte_at_pc();
+                    self.read_stack_pointer();
+                    // PC is at the high byte of the target, so the return address (minus one)
+                    // is the JSR's last byte
+                    self.push_word_to_stack(self.pc);
+                    let high = self.read_byte(self.pc);
+                    self.pc = u16::from_le_bytes([low, high]);
                 }
             },
-        }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn loading_and_storing() {
-        let bin = include_bytes!("../tests/fixtures/loading_and_storing/test.bin");
-        let mut memory = Memory::new();
-        memory.write_bytes(0x1000, bin);
-        let mut processor = Processor::new(memory);
-        // Using 0 byte for program termination for now (which corresponds to the BRK instruction)
-        while processor.peek_byte_at_pc() != 0 {
-            processor.process_next_instruction();
-        }
-
-        assert_eq!(
-            processor.memory.read_byte(0x1000),
-            processor.memory.read_byte(0x100D)
-        );
-    }
-
-    #[test]
-    fn unsigned_addition() {
-        let bin = include_bytes!("../tests/fixtures/unsigned_addition/test.bin");
-        let mut memory = Memory::new();
+            Instruction::Illegal(instruction) => match instruction {
+                IllegalInstruction::SLO => {
+                    let data = self.modify_operand(addr, addressing_mode, Self::shift_left);
+                    self.a |= data;
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                IllegalInstruction::RLA => {
+                    let data = self.modify_operand(addr, addressing_mode, Self::rotate_left);
+                    self.a &= data;
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                IllegalInstruction::SRE => {
+                    let data = self.modify_operand(addr, addressing_mode, Self::shift_right);
+                    self.a ^= data;
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                IllegalInstruction::RRA => {
+                    // ADC uses the carry shifted out by ROR
+                    let data = self.modify_operand(addr, addressing_mode, Self::rotate_right);
+                    self.add_with_carry(data);
+                }
+                IllegalInstruction::DCP => {
+                    let data = self.modify_operand(addr, addressing_mode, Self::decrement);
+                    self.compare(self.a, data);
+                }
+                IllegalInstruction::ISC => {
+                    let data = self.modify_operand(addr, addressing_mode, Self::increment);
+                    self.subtract_with_borrow(data);
+                }
+                IllegalInstruction::SAX => self.write_byte(addr, self.a & self.x),
+                IllegalInstruction::LAX => {
+                    self.a = self.read_byte(addr);
+                    self.x = self.a;
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                IllegalInstruction::LAS => {
+                    let value = self.read_byte(addr) & self.s;
+                    self.a = value;
+                    self.x = value;
+                    self.s = value;
+                    self.update_zero_and_negative_flags(value);
+                }
+                IllegalInstruction::ANC => {
+                    self.a &= self.read_byte(addr);
+                    self.update_zero_and_negative_flags(self.a);
+                    self.p
+                        .update(StatusFlag::Carry, self.p.get(StatusFlag::Negative));
+                }
+                IllegalInstruction::ALR => {
+                    let data = self.read_byte(addr);
+                    self.a = self.shift_right(self.a & data);
+                }
+                IllegalInstruction::ARR => {
+                    let data = self.read_byte(addr);
+                    self.and_rotate_right(data);
+                }
+                IllegalInstruction::SBX => {
+                    // Like CMP, so neither the decimal flag nor the carry in are used
+                    let data = self.read_byte(addr);
+                    let value = self.a & self.x;
+                    self.p.update(StatusFlag::Carry, value >= data);
+                    self.x = value.wrapping_sub(data);
+                    self.update_zero_and_negative_flags(self.x);
+                }
+                IllegalInstruction::USBC => {
+                    let data = self.read_byte(addr);
+                    self.subtract_with_borrow(data);
+                }
+                IllegalInstruction::XAA => {
+                    let data = self.read_byte(addr);
+                    self.a = (self.a | self.magic_constant) & self.x & data;
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                IllegalInstruction::LXA => {
+                    let data = self.read_byte(addr);
+                    self.a = (self.a | self.magic_constant) & data;
+                    self.x = self.a;
+                    self.update_zero_and_negative_flags(self.a);
+                }
+                IllegalInstruction::SHA => {
+                    self.store_and_high_byte(addr, addressing_mode, self.a & self.x)
+                }
+                IllegalInstruction::SHX => self.store_and_high_byte(addr, addressing_mode, self.x),
+                IllegalInstruction::SHY => self.store_and_high_byte(addr, addressing_mode, self.y),
+                IllegalInstruction::TAS => {
+                    self.s = self.a & self.x;
+                    self.store_and_high_byte(addr, addressing_mode, self.s);
+                }
+                IllegalInstruction::NOP => {
+                    // NOPs with an operand still read it
+                    if addressing_mode != AddressingMode::Implicit {
+                        self.read_byte(addr);
+                    }
+                }
+                IllegalInstruction::JAM => self.jammed = Some(instruction_pc),
+            },
+            Instruction::Cmos(instruction) => match instruction {
+                CmosInstruction::BRA => self.branch_if(true, addr),
+                CmosInstruction::PHX => self.push_to_stack(self.x),
+                CmosInstruction::PHY => self.push_to_stack(self.y),
+                CmosInstruction::PLX => {
+                    self.read_stack_pointer();
+                    self.x = self.pop_from_stack();
+                    self.update_zero_and_negative_flags(self.x);
+                }
+                CmosInstruction::PLY => {
+                    self.read_stack_pointer();
+                    self.y = self.pop_from_stack();
+                    self.update_zero_and_negative_flags(self.y);
+                }
+                CmosInstruction::STZ => self.write_byte(addr, 0),
+                // Z is set as BIT would, then the bits set in A are cleared or set in memory
+                CmosInstruction::TRB => {
+                    self.modify_operand(addr, addressing_mode, |cpu, data| {
+                        cpu.p.update(StatusFlag::Zero, (data & cpu.a) == 0);
+                        data & !cpu.a
+                    });
+                }
+                CmosInstruction::TSB => {
+                    self.modify_operand(addr, addressing_mode, |cpu, data| {
+                        cpu.p.update(StatusFlag::Zero, (data & cpu.a) == 0);
+                        data | cpu.a
+                    });
+                }
+                CmosInstruction::RMB(bit) => {
+                    self.modify_operand(addr, addressing_mode, |_, data| data & !(1 << bit));
+                }
+                CmosInstruction::SMB(bit) => {
+                    self.modify_operand(addr, addressing_mode, |_, data| data | (1 << bit));
+                }
+                CmosInstruction::BBR(bit) | CmosInstruction::BBS(bit) => {
+                    let data = self.read_byte(addr);
+                    self.read_byte(addr);
+                    let offset = self.take_byte_at_pc() as i8;
+                    let target = self.pc.wrapping_add_signed(offset as i16);
+                    let bit_set = (data & (1 << bit)) != 0;
+                    let branch_if_set = matches!(instruction, CmosInstruction::BBS(_));
+                    self.branch_if(bit_set == branch_if_set, target);
+                }
+                CmosInstruction::WAI => {
+                    self.read_byte(self.pc);
+                    self.waiting = true;
+                }
+                CmosInstruction::STP => {
+                    self.read_byte(self.pc);
+                    self.jammed = Some(instruction_pc);
+                }
+                CmosInstruction::NOP => {
+                    if value == 0x5C {
+                        // Reads its operand as an address, then spends 5 cycles reading $FFxx
+                        for _ in 0..5 {
+                            self.read_byte(0xFF00 | (addr & 0x00FF));
+                        }
+                    } else if addressing_mode != AddressingMode::Implicit {
+                        self.read_byte(addr);
+                    }
+                }
+            },
+        }
+        self.irq_disabled_at_poll = match instruction {
+            Instruction::SingleByte(
+                SingleByteInstruction::CLI
+                | SingleByteInstruction::SEI
+                | SingleByteInstruction::PLP,
+            ) => irq_disabled,
+            _ => self.p.get(StatusFlag::InterruptDisable),
+        };
+        Ok(match (instruction, addressing_mode) {
+            // JSR's target is only known once it has jumped
+            (Instruction::SpecialCase(SpecialCase::JSRABS), _) => Some(self.pc),
+            (_, AddressingMode::Implicit | AddressingMode::Accumulator) => None,
+            _ => Some(addr),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+    use crate::{
+        banking::{BankSize, BankedWindow, UxRom, WindowMapper},
+        io_port::IoPort,
+        opcodes::opcode_table,
+    };
+    /// Runs `program` from 0x1000 until it reaches a BRK
+    fn run_program(program: &[u8]) -> Processor {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, program);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        processor
+    }
+
+    #[test]
+    fn adc_sets_overflow() {
+        // CLC; LDA #$50; ADC #$50
+        let processor = run_program(&[0x18, 0xA9, 0x50, 0x69, 0x50]);
+        assert_eq!(processor.a, 0xA0);
+        assert!(processor.p.get(StatusFlag::Overflow));
+        assert!(processor.p.get(StatusFlag::Negative));
+        assert!(!processor.p.get(StatusFlag::Carry));
+    }
+
+    #[test]
+    fn adc_sets_carry_and_zero() {
+        // CLC; LDA #$FF; ADC #$01
+        let processor = run_program(&[0x18, 0xA9, 0xFF, 0x69, 0x01]);
+        assert_eq!(processor.a, 0);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Zero));
+        assert!(!processor.p.get(StatusFlag::Overflow));
+    }
+
+    #[test]
+    fn sbc_without_borrow() {
+        // SEC; LDA #$50; SBC #$30
+        let processor = run_program(&[0x38, 0xA9, 0x50, 0xE9, 0x30]);
+        assert_eq!(processor.a, 0x20);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(!processor.p.get(StatusFlag::Overflow));
+    }
+
+    #[test]
+    fn sbc_with_borrow_and_overflow() {
+        // SEC; LDA #$50; SBC #$B0
+        let processor = run_program(&[0x38, 0xA9, 0x50, 0xE9, 0xB0]);
+        assert_eq!(processor.a, 0xA0);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Overflow));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn sbc_uses_carry_as_borrow() {
+        // CLC; LDA #$05; SBC #$05
+        let processor = run_program(&[0x18, 0xA9, 0x05, 0xE9, 0x05]);
+        assert_eq!(processor.a, 0xFF);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn decimal_adc() {
+        // SED; CLC; LDA #$19; ADC #$28
+        let processor = run_program(&[0xF8, 0x18, 0xA9, 0x19, 0x69, 0x28]);
+        assert_eq!(processor.a, 0x47);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        // SED; SEC; LDA #$58; ADC #$46
+        let processor = run_program(&[0xF8, 0x38, 0xA9, 0x58, 0x69, 0x46]);
+        assert_eq!(processor.a, 0x05);
+        assert!(processor.p.get(StatusFlag::Carry));
+    }
+
+    #[test]
+    fn decimal_sbc() {
+        // SED; SEC; LDA #$50; SBC #$01
+        let processor = run_program(&[0xF8, 0x38, 0xA9, 0x50, 0xE9, 0x01]);
+        assert_eq!(processor.a, 0x49);
+        assert!(processor.p.get(StatusFlag::Carry));
+        // SED; SEC; LDA #$00; SBC #$01
+        let processor = run_program(&[0xF8, 0x38, 0xA9, 0x00, 0xE9, 0x01]);
+        assert_eq!(processor.a, 0x99);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn decimal_mode_known_results() {
+        // (A, operand, carry in, A out, carry out, Z). On the NMOS 6502, Z comes from the
+        // binary result rather than the decimal one
+        let adc = [
+            (0x12, 0x34, false, 0x46, false, false),
+            (0x15, 0x26, false, 0x41, false, false),
+            (0x58, 0x46, true, 0x05, true, false),
+            (0x81, 0x92, false, 0x73, true, false),
+            (0x99, 0x01, false, 0x00, true, false),
+            (0x50, 0x50, false, 0x00, true, false),
+            (0x00, 0x00, false, 0x00, false, true),
+        ];
+        let sbc = [
+            (0x46, 0x12, true, 0x34, true, false),
+            (0x40, 0x13, true, 0x27, true, false),
+            (0x32, 0x02, false, 0x29, true, false),
+            (0x12, 0x21, true, 0x91, false, false),
+            (0x21, 0x34, true, 0x87, false, false),
+            (0x00, 0x00, true, 0x00, true, true),
+        ];
+        let mut processor = Processor::new(Memory::new());
+        processor.p.set(StatusFlag::Decimal);
+        let cases = adc.iter().map(|case| (false, case));
+        for (subtract, &(a, b, carry, value, carry_out, zero)) in
+            cases.chain(sbc.iter().map(|case| (true, case)))
+        {
+            processor.a = a;
+            processor.p.update(StatusFlag::Carry, carry);
+            if subtract {
+                processor.subtract_with_borrow(b);
+            } else {
+                processor.add_with_carry(b);
+            }
+            let case = (a, b, carry, subtract);
+            assert_eq!(processor.a, value, "{:02X?}", case);
+            assert_eq!(
+                processor.p.get(StatusFlag::Carry),
+                carry_out,
+                "{:02X?}",
+                case
+            );
+            assert_eq!(processor.p.get(StatusFlag::Zero), zero, "{:02X?}", case);
+        }
+        // N and V come from the intermediate result: $99 + $01 passes through $A0
+        processor.a = 0x99;
+        processor.p.clear(StatusFlag::Carry);
+        processor.add_with_carry(0x01);
+        assert!(processor.p.get(StatusFlag::Negative));
+        processor.a = 0x81;
+        processor.add_with_carry(0x92);
+        assert!(processor.p.get(StatusFlag::Overflow));
+    }
+
+    #[test]
+    fn asl_memory() {
+        // LDA #$C1; STA $20; ASL $20
+        let processor = run_program(&[0xA9, 0xC1, 0x85, 0x20, 0x06, 0x20]);
+        assert_eq!(processor.memory.read_byte(0x20), 0x82);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn rol_accumulator() {
+        // SEC; LDA #$80; ROL A
+        let processor = run_program(&[0x38, 0xA9, 0x80, 0x2A]);
+        assert_eq!(processor.a, 0x01);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(!processor.p.get(StatusFlag::Zero));
+    }
+
+    #[test]
+    fn rol_memory() {
+        // CLC; LDA #$40; STA $1100; ROL $1100
+        let processor = run_program(&[0x18, 0xA9, 0x40, 0x8D, 0x00, 0x11, 0x2E, 0x00, 0x11]);
+        assert_eq!(processor.memory.read_byte(0x1100), 0x80);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn lsr_accumulator() {
+        // LDA #$01; LSR A
+        let processor = run_program(&[0xA9, 0x01, 0x4A]);
+        assert_eq!(processor.a, 0);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Zero));
+    }
+
+    #[test]
+    fn lsr_memory_zero_page_x() {
+        // LDX #$02; LDA #$84; STA $32; LSR $30,X
+        let processor = run_program(&[0xA2, 0x02, 0xA9, 0x84, 0x85, 0x32, 0x56, 0x30]);
+        assert_eq!(processor.memory.read_byte(0x32), 0x42);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(!processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn ror_accumulator() {
+        // SEC; LDA #$01; ROR A
+        let processor = run_program(&[0x38, 0xA9, 0x01, 0x6A]);
+        assert_eq!(processor.a, 0x80);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn ror_memory() {
+        // CLC; LDA #$02; STA $40; ROR $40
+        let processor = run_program(&[0x18, 0xA9, 0x02, 0x85, 0x40, 0x66, 0x40]);
+        assert_eq!(processor.memory.read_byte(0x40), 0x01);
+        assert!(!processor.p.get(StatusFlag::Carry));
+    }
+
+    #[test]
+    fn cpy() {
+        // LDY #$10; CPY #$10
+        let processor = run_program(&[0xA0, 0x10, 0xC0, 0x10]);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Zero));
+        // LDY #$10; CPY #$20
+        let processor = run_program(&[0xA0, 0x10, 0xC0, 0x20]);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(!processor.p.get(StatusFlag::Zero));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn cmp_sets_carry_when_greater() {
+        // LDA #$30; CMP #$20
+        let processor = run_program(&[0xA9, 0x30, 0xC9, 0x20]);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(!processor.p.get(StatusFlag::Zero));
+        assert!(!processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn inc_and_dec_wrap() {
+        // LDA #$FF; STA $10; INC $10; DEC $11
+        let processor = run_program(&[0xA9, 0xFF, 0x85, 0x10, 0xE6, 0x10, 0xC6, 0x11]);
+        assert_eq!(processor.memory.read_byte(0x10), 0x00);
+        assert_eq!(processor.memory.read_byte(0x11), 0xFF);
+        assert!(processor.p.get(StatusFlag::Negative));
+        // DEC must not touch the accumulator
+        assert_eq!(processor.a, 0xFF);
+    }
+
+    #[test]
+    fn bit() {
+        // LDA #$01; STA $10; LDA #$C0; STA $11; LDA #$01; BIT $11
+        let processor = run_program(&[
+            0xA9, 0x01, 0x85, 0x10, 0xA9, 0xC0, 0x85, 0x11, 0xA9, 0x01, 0x24, 0x11,
+        ]);
+        assert!(processor.p.get(StatusFlag::Zero));
+        assert!(processor.p.get(StatusFlag::Negative));
+        assert!(processor.p.get(StatusFlag::Overflow));
+    }
+
+    #[test]
+    fn stx_and_ldx_index_with_y() {
+        // LDY #$04; LDX #$AB; STX $10,Y; LDX #$00; LDX $10,Y
+        let processor = run_program(&[0xA0, 0x04, 0xA2, 0xAB, 0x96, 0x10, 0xA2, 0x00, 0xB6, 0x10]);
+        assert_eq!(processor.memory.read_byte(0x14), 0xAB);
+        assert_eq!(processor.x, 0xAB);
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn backwards_branch_loop() {
+        // LDX #$03; loop: DEX; BNE loop
+        let processor = run_program(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD]);
+        assert_eq!(processor.x, 0);
+        assert_eq!(processor.pc, 0x1005);
+    }
+
+    #[test]
+    fn forward_branch_skips() {
+        // SEC; BCS +2; LDA #$01; LDX #$02
+        let processor = run_program(&[0x38, 0xB0, 0x02, 0xA9, 0x01, 0xA2, 0x02]);
+        assert_eq!(processor.a, 0);
+        assert_eq!(processor.x, 2);
+    }
+
+    #[test]
+    fn jmp_absolute() {
+        // JMP $1005; LDA #$01; LDX #$02
+        let processor = run_program(&[0x4C, 0x05, 0x10, 0xA9, 0x01, 0xA2, 0x02]);
+        assert_eq!(processor.a, 0);
+        assert_eq!(processor.x, 2);
+    }
+
+    #[test]
+    fn jsr_and_rts() {
+        // JSR $1007; LDX #$01; BRK; BRK; LDY #$02; RTS
+        let processor = run_program(&[0x20, 0x07, 0x10, 0xA2, 0x01, 0x00, 0x00, 0xA0, 0x02, 0x60]);
+        assert_eq!(processor.x, 1);
+        assert_eq!(processor.y, 2);
+        assert_eq!(processor.s, 0xFD);
+        // Return address minus one is pushed high byte first
+        assert_eq!(processor.memory.read_byte(0x01FD), 0x10);
+        assert_eq!(processor.memory.read_byte(0x01FC), 0x02);
+    }
+
+    #[test]
+    fn pha_and_pla() {
+        // LDA #$42; PHA; LDA #$00; PLA
+        let processor = run_program(&[0xA9, 0x42, 0x48, 0xA9, 0x00, 0x68]);
+        assert_eq!(processor.a, 0x42);
+        assert_eq!(processor.memory.read_byte(0x01FD), 0x42);
+        assert!(!processor.p.get(StatusFlag::Zero));
+    }
+
+    #[test]
+    fn php_and_plp() {
+        // SEC; SED; PHP; CLC; CLD; PLP
+        let processor = run_program(&[0x38, 0xF8, 0x08, 0x18, 0xD8, 0x28]);
+        assert_eq!(processor.memory.read_byte(0x01FD), 0b00111101);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Decimal));
+        assert!(!processor.p.get(StatusFlag::Break));
+    }
+
+    #[test]
+    fn tsx_and_txs() {
+        // LDX #$80; TXS; LDX #$00; TSX
+        let processor = run_program(&[0xA2, 0x80, 0x9A, 0xA2, 0x00, 0xBA]);
+        assert_eq!(processor.s, 0x80);
+        assert_eq!(processor.x, 0x80);
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn absolute_indexed_crosses_pages() {
+        // LDX #$10; LDY #$20; LDA #$5A; STA $10F8,X; LDA #$00; LDA $10E8,Y
+        let processor = run_program(&[
+            0xA2, 0x10, 0xA0, 0x20, 0xA9, 0x5A, 0x9D, 0xF8, 0x10, 0xA9, 0x00, 0xB9, 0xE8, 0x10,
+        ]);
+        assert_eq!(processor.memory.read_byte(0x1108), 0x5A);
+        assert_eq!(processor.a, 0x5A);
+    }
+
+    #[test]
+    fn indexed_indirect() {
+        // LDX #$04; LDA #$34; STA $24; LDA #$12; STA $25; LDA #$77; STA ($20,X)
+        let processor = run_program(&[
+            0xA2, 0x04, 0xA9, 0x34, 0x85, 0x24, 0xA9, 0x12, 0x85, 0x25, 0xA9, 0x77, 0x81, 0x20,
+        ]);
+        assert_eq!(processor.memory.read_byte(0x1234), 0x77);
+    }
+
+    #[test]
+    fn indexed_indirect_wraps_in_zero_page() {
+        let mut memory = Memory::new();
+        // Pointer at $FF/$00, reached through $80 + $7F
+        memory.write_byte(0xFF, 0x00);
+        memory.write_byte(0x00, 0x20);
+        memory.write_byte(0x2000, 0x99);
+        // LDX #$7F; LDA ($80,X)
+        memory.write_bytes(0x1000, &[0xA2, 0x7F, 0xA1, 0x80]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.a, 0x99);
+    }
+
+    #[test]
+    fn indirect_indexed() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x40, &[0xF0, 0x20]);
+        memory.write_byte(0x2100, 0x42);
+        // LDY #$10; LDA ($40),Y
+        memory.write_bytes(0x1000, &[0xA0, 0x10, 0xB1, 0x40]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.a, 0x42);
+    }
+
+    #[test]
+    fn indirect_indexed_pointer_wraps_in_zero_page() {
+        let mut memory = Memory::new();
+        memory.write_byte(0xFF, 0x00);
+        memory.write_byte(0x00, 0x30);
+        // LDY #$05; LDA #$AB; STA ($FF),Y
+        memory.write_bytes(0x1000, &[0xA0, 0x05, 0xA9, 0xAB, 0x91, 0xFF]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.memory.read_byte(0x3005), 0xAB);
+    }
+
+    #[test]
+    fn string_copy() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x2000, b"6502\0");
+        // Source pointer in $10, destination pointer in $12
+        memory.write_bytes(0x10, &[0x00, 0x20, 0x00, 0x30]);
+        // LDY #$00; loop: LDA ($10),Y; STA ($12),Y; BEQ done; INY; JMP loop; done:
+        memory.write_bytes(
+            0x1000,
+            &[
+                0xA0, 0x00, 0xB1, 0x10, 0x91, 0x12, 0xF0, 0x04, 0xC8, 0x4C, 0x02, 0x10,
+            ],
+        );
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.memory.read_bytes(0x3000, 5), b"6502\0");
+    }
+
+    #[test]
+    fn jmp_indirect() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1100, &[0x08, 0x10]);
+        // JMP ($1100); LDA #$01; BRK; LDX #$02
+        memory.write_bytes(
+            0x1000,
+            &[0x6C, 0x00, 0x11, 0xA9, 0x01, 0x00, 0x00, 0x00, 0xA2, 0x02],
+        );
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.a, 0);
+        assert_eq!(processor.x, 2);
+    }
+
+    #[test]
+    fn jmp_indirect_page_wrap_bug() {
+        let mut memory = Memory::new();
+        // The high byte comes from $1100, not $1200
+        memory.write_byte(0x11FF, 0x00);
+        memory.write_byte(0x1100, 0x30);
+        memory.write_byte(0x1200, 0x40);
+        memory.write_bytes(0x1000, &[0x6C, 0xFF, 0x11]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x3000);
+    }
+
+    // Places `program` at $1000, with IRQ/BRK handler at $2000 and NMI handler at $3000
+    fn processor_with_handlers(
+        program: &[u8],
+        irq_handler: &[u8],
+        nmi_handler: &[u8],
+    ) -> Processor {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, program);
+        memory.write_bytes(0x2000, irq_handler);
+        memory.write_bytes(0x3000, nmi_handler);
+        memory.write_bytes(NMI_VECTOR, &[0x00, 0x30]);
+        memory.write_bytes(RESET_VECTOR, &[0x00, 0x40]);
+        memory.write_bytes(IRQ_VECTOR, &[0x00, 0x20]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        // Start with interrupts enabled
+        processor.p.clear(StatusFlag::InterruptDisable);
+        processor.irq_disabled_at_poll = false;
+        processor
+    }
+
+    #[test]
+    fn brk_jumps_through_vector() {
+        // SEC; BRK
+        let mut processor = processor_with_handlers(&[0x38, 0x00], &[], &[]);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x2000);
+        assert!(processor.p.get(StatusFlag::InterruptDisable));
+        assert_eq!(processor.s, 0xFA);
+        // Return address skips the padding byte
+        assert_eq!(processor.memory.read_byte(0x01FD), 0x10);
+        assert_eq!(processor.memory.read_byte(0x01FC), 0x03);
+        // Pushed with the break and unused bits set
+        assert_eq!(processor.memory.read_byte(0x01FB), 0b00110001);
+    }
+
+    #[test]
+    fn rti_returns_from_brk() {
+        // BRK; padding; LDA #$05 / handler: RTI
+        let mut processor = processor_with_handlers(&[0x00, 0xFF, 0xA9, 0x05], &[0x40], &[]);
+        for _ in 0..3 {
+            processor.process_next_instruction().unwrap();
+        }
+        assert_eq!(processor.a, 0x05);
+        assert_eq!(processor.s, 0xFD);
+        assert!(!processor.p.get(StatusFlag::InterruptDisable));
+        assert!(!processor.p.get(StatusFlag::Break));
+    }
+
+    #[test]
+    fn irq_is_masked_by_interrupt_disable() {
+        // SEI; NOP; NOP; NOP
+        let mut processor = processor_with_handlers(&[0x78, 0xEA, 0xEA, 0xEA], &[], &[]);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        processor.set_irq_line(true);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1004);
+    }
+
+    #[test]
+    fn cli_delays_irq_by_one_instruction() {
+        // SEI; NOP; CLI; NOP; NOP
+        let mut processor = processor_with_handlers(&[0x78, 0xEA, 0x58, 0xEA, 0xEA], &[], &[]);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        processor.set_irq_line(true);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1004);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x2000);
+    }
+
+    #[test]
+    fn irq_pushes_status_without_break() {
+        // NOP; NOP
+        let mut processor = processor_with_handlers(&[0xEA, 0xEA], &[], &[]);
+        processor.process_next_instruction().unwrap();
+        processor.set_irq_line(true);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x2000);
+        assert!(processor.p.get(StatusFlag::InterruptDisable));
+        assert_eq!(processor.memory.read_byte(0x01FD), 0x10);
+        assert_eq!(processor.memory.read_byte(0x01FC), 0x01);
+        assert_eq!(processor.memory.read_byte(0x01FB), 0b00100000);
+    }
+
+    #[test]
+    fn irq_is_level_triggered() {
+        // Handler: RTI
+        let mut processor = processor_with_handlers(&[0xEA], &[0x40], &[]);
+        processor.set_irq_line(true);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x2000);
+        // RTI clears I again, and the line is still asserted
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1000);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x2000);
+        // Released, so the program continues
+        processor.set_irq_line(false);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1001);
+    }
+
+    #[test]
+    fn nmi_is_edge_triggered() {
+        // SEI; NOP / handler: RTI
+        let mut processor = processor_with_handlers(&[0x78, 0xEA, 0xEA], &[], &[0x40]);
+        processor.process_next_instruction().unwrap();
+        processor.set_nmi_line(true);
+        // NMI ignores the I flag
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x3000);
+        assert_eq!(processor.memory.read_byte(0x01FB), 0b00100100);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1001);
+        // Still asserted, but no new edge
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1002);
+        processor.set_nmi_line(false);
+        processor.trigger_nmi();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x3000);
+    }
+
+    #[test]
+    fn nmi_takes_priority_over_irq() {
+        let mut processor = processor_with_handlers(&[0xEA], &[], &[]);
+        processor.set_irq_line(true);
+        processor.trigger_nmi();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x3000);
+    }
+
+    #[test]
+    fn power_on_boots_from_reset_vector() {
+        let mut memory = Memory::new();
+        memory.write_bytes(RESET_VECTOR, &[0x34, 0x12]);
+        let processor = Processor::new(memory);
+        assert_eq!(processor.pc, 0x1234);
+        assert_eq!(processor.s, 0xFD);
+        assert!(processor.p.get(StatusFlag::InterruptDisable));
+    }
+
+    #[test]
+    fn reset_loads_vector() {
+        let mut processor = processor_with_handlers(&[0xEA], &[], &[]);
+        processor.reset();
+        assert_eq!(processor.pc, 0x4000);
+        assert_eq!(processor.s, 0xFA);
+        assert!(processor.p.get(StatusFlag::InterruptDisable));
+    }
+
+    #[test]
+    fn opcode_cycles_match_table() {
+        for variant in Variant::ALL {
+            for (value, opcode) in opcode_table(variant).iter().enumerate() {
+                // Branches are covered separately, as whether they're taken depends on the flags
+                if matches!(
+                    opcode.addressing_mode,
+                    AddressingMode::Relative | AddressingMode::ZeroPageRelative
+                ) {
+                    continue;
+                }
+                let mut processor = processor_for(variant, &[value as u8, 0x00, 0x00]);
+                let start = processor.cycles();
+                // Operands of zero keep every access in the zero page. JAM and STP count
+                // their cycles even though they return an error
+                let _ = processor.process_next_instruction();
+                assert_eq!(
+                    processor.cycles() - start,
+                    opcode.cycles as u64,
+                    "opcode ${:02X} on {:?}",
+                    value,
+                    variant
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn opcodes_advance_pc_by_length() {
+        for variant in Variant::ALL {
+            for (value, opcode) in opcode_table(variant).iter().enumerate() {
+                let changes_flow = matches!(
+                    opcode.instruction,
+                    Instruction::GroupThree(Group3Instruction::JMP)
+                        | Instruction::SpecialCase(_)
+                        | Instruction::SingleByte(
+                            SingleByteInstruction::BRK
+                                | SingleByteInstruction::RTI
+                                | SingleByteInstruction::RTS
+                        )
+                );
+                if changes_flow {
+                    continue;
+                }
+                let mut processor = processor_for(variant, &[value as u8, 0x00, 0x00]);
+                let _ = processor.process_next_instruction();
+                // Branch offsets of 0 land on the next instruction whether taken or not
+                assert_eq!(
+                    processor.pc,
+                    0x1000 + opcode.length as u16,
+                    "opcode ${:02X} on {:?}",
+                    value,
+                    variant
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn power_on_takes_seven_cycles() {
+        let processor = Processor::new(Memory::new());
+        assert_eq!(processor.cycles(), 7);
+    }
+
+    #[test]
+    fn indexed_reads_take_a_cycle_to_cross_a_page() {
+        // LDX #$01; LDA $10FE,X; LDA $10FF,X; STA $10FE,X
+        let mut memory = Memory::new();
+        memory.write_bytes(
+            0x1000,
+            &[
+                0xA2, 0x01, 0xBD, 0xFE, 0x10, 0xBD, 0xFF, 0x10, 0x9D, 0xFE, 0x10,
+            ],
+        );
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.process_next_instruction().unwrap(), 4);
+        assert_eq!(processor.process_next_instruction().unwrap(), 5);
+        // Stores always take the extra cycle
+        assert_eq!(processor.process_next_instruction().unwrap(), 5);
+    }
+
+    #[test]
+    fn indirect_indexed_takes_a_cycle_to_cross_a_page() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x10, &[0xFF, 0x20]);
+        // LDY #$00; LDA ($10),Y; INY; LDA ($10),Y
+        memory.write_bytes(0x1000, &[0xA0, 0x00, 0xB1, 0x10, 0xC8, 0xB1, 0x10]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.process_next_instruction().unwrap(), 5);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.process_next_instruction().unwrap(), 6);
+    }
+
+    #[test]
+    fn branch_cycles() {
+        let mut memory = Memory::new();
+        // $10F0: LDA #$01; BEQ +0; BNE +0; BNE +$10
+        memory.write_bytes(0x10F0, &[0xA9, 0x01, 0xF0, 0x00, 0xD0, 0x00, 0xD0, 0x10]);
+        let mut processor = Processor::with_start_address(memory, 0x10F0);
+        processor.process_next_instruction().unwrap();
+        // Not taken
+        assert_eq!(processor.process_next_instruction().unwrap(), 2);
+        // Taken within the page
+        assert_eq!(processor.process_next_instruction().unwrap(), 3);
+        // Taken onto the next page
+        assert_eq!(processor.process_next_instruction().unwrap(), 4);
+        assert_eq!(processor.pc, 0x1108);
+    }
+
+    #[test]
+    fn interrupts_take_seven_cycles() {
+        let mut processor = processor_with_handlers(&[0xEA], &[], &[]);
+        processor.set_irq_line(true);
+        assert_eq!(processor.process_next_instruction().unwrap(), 7);
+        processor.trigger_nmi();
+        assert_eq!(processor.process_next_instruction().unwrap(), 7);
+    }
+
+    #[test]
+    fn running_cycle_total() {
+        // LDX #$03; loop: DEX; BNE loop
+        let processor = run_program(&[0xA2, 0x03, 0xCA, 0xD0, 0xFD]);
+        // Power on, LDX, then two taken and one untaken loop
+        assert_eq!(processor.cycles(), 7 + 2 + (2 + 3) * 2 + (2 + 2));
+    }
+
+    fn read(address: u16, data: u8) -> BusCycle {
+        BusCycle {
+            address,
+            data,
+            access: BusAccess::Read,
+        }
+    }
+    fn write(address: u16, data: u8) -> BusCycle {
+        BusCycle {
+            address,
+            data,
+            access: BusAccess::Write,
+        }
+    }
+    // Steps through the next instruction a cycle at a time, returning its bus accesses
+    fn step_instruction(processor: &mut Processor) -> Vec<BusCycle> {
+        let mut cycles = vec![processor.step_cycle()];
+        while processor.is_mid_instruction() {
+            cycles.push(processor.step_cycle());
+        }
+        cycles
+    }
+
+    #[test]
+    fn jsr_bus_trace() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0x20, 0x34, 0x12]);
+        memory.write_byte(0x01FD, 0xEE);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(
+            step_instruction(&mut processor),
+            [
+                read(0x1000, 0x20),
+                read(0x1001, 0x34),
+                read(0x01FD, 0xEE),
+                write(0x01FD, 0x10),
+                write(0x01FC, 0x02),
+                read(0x1002, 0x12),
+            ]
+        );
+        assert_eq!(processor.pc, 0x1234);
+    }
+
+    #[test]
+    fn rts_bus_trace() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0x60, 0xFF]);
+        memory.write_bytes(0x01FB, &[0xAA, 0x02, 0x20]);
+        memory.write_byte(0x2002, 0x55);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.s = 0xFB;
+        assert_eq!(
+            step_instruction(&mut processor),
+            [
+                read(0x1000, 0x60),
+                read(0x1001, 0xFF),
+                read(0x01FB, 0xAA),
+                read(0x01FC, 0x02),
+                read(0x01FD, 0x20),
+                read(0x2002, 0x55),
+            ]
+        );
+        assert_eq!(processor.pc, 0x2003);
+    }
+
+    #[test]
+    fn read_modify_write_writes_twice() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0xE6, 0x10]);
+        memory.write_byte(0x10, 0x41);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        let trace: Vec<_> = (0..4).map(|_| processor.step_cycle()).collect();
+        assert_eq!(
+            trace,
+            [
+                read(0x1000, 0xE6),
+                read(0x1001, 0x10),
+                read(0x0010, 0x41),
+                write(0x0010, 0x41),
+            ]
+        );
+        // The result hasn't been written yet
+        assert_eq!(processor.memory.read_byte(0x10), 0x41);
+        assert_eq!(processor.step_cycle(), write(0x0010, 0x42));
+        assert_eq!(processor.memory.read_byte(0x10), 0x42);
+        assert!(!processor.is_mid_instruction());
+    }
+
+    #[test]
+    fn absolute_indexed_dummy_read() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0xBD, 0xFF, 0x20]);
+        memory.write_byte(0x2000, 0x11);
+        memory.write_byte(0x2100, 0x22);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.x = 1;
+        assert_eq!(
+            step_instruction(&mut processor),
+            [
+                read(0x1000, 0xBD),
+                read(0x1001, 0xFF),
+                read(0x1002, 0x20),
+                read(0x2000, 0x11),
+                read(0x2100, 0x22),
+            ]
+        );
+        assert_eq!(processor.a, 0x22);
+    }
+
+    #[test]
+    fn registers_change_on_last_cycle() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0xA9, 0x42]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        let cycles = processor.cycles();
+        processor.step_cycle();
+        assert_eq!(processor.a, 0);
+        assert_eq!(processor.pc, 0x1000);
+        assert_eq!(processor.cycles(), cycles + 1);
+        processor.step_cycle();
+        assert_eq!(processor.a, 0x42);
+        assert_eq!(processor.pc, 0x1002);
+        assert_eq!(processor.cycles(), cycles + 2);
+    }
+
+    #[test]
+    fn process_next_instruction_finishes_stepped_instruction() {
+        let mut memory = Memory::new();
+        // INC $1234; NOP
+        memory.write_bytes(0x1000, &[0xEE, 0x34, 0x12, 0xEA]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.step_cycle();
+        processor.step_cycle();
+        assert_eq!(processor.process_next_instruction().unwrap(), 4);
+        assert_eq!(processor.memory.read_byte(0x1234), 1);
+        assert_eq!(processor.pc, 0x1003);
+    }
+
+    #[test]
+    fn nmi_during_stepped_instruction_is_taken_after_it() {
+        // INC $10; NOP
+        let mut processor = processor_with_handlers(&[0xE6, 0x10, 0xEA], &[], &[]);
+        processor.step_cycle();
+        processor.trigger_nmi();
+        step_instruction(&mut processor);
+        assert_eq!(processor.memory.read_byte(0x10), 1);
+        assert_eq!(processor.pc, 0x1002);
+        step_instruction(&mut processor);
+        assert_eq!(processor.pc, 0x3000);
+    }
+
+    #[test]
+    fn stepping_matches_whole_instructions() {
+        #[rustfmt::skip]
+        let program = [
+            // LDX #$06; LDA #$00; STA $1500; LDA #$01; STA $1501
+            0xA2, 0x06, 0xA9, 0x00, 0x8D, 0x00, 0x15, 0xA9, 0x01, 0x8D, 0x01, 0x15,
+            // loop: LDA $1500; ADC $1501; STA $1500; ADC $1501; STA $1501
+            0xAD, 0x00, 0x15, 0x6D, 0x01, 0x15, 0x8D, 0x00, 0x15, 0x6D, 0x01, 0x15, 0x8D, 0x01,
+            0x15,
+            // PHA; ROL $1500,X; JSR sub; DEX; BNE loop; BRK; sub: PLA; PHA; RTS
+            0x48, 0x3E, 0x00, 0x15, 0x20, 0x26, 0x10, 0xCA, 0xD0, 0xE7, 0x00, 0x68, 0x48, 0x60,
+        ];
+        let whole = run_program(&program);
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &program);
+        let mut stepped = Processor::with_start_address(memory, 0x1000);
+        while stepped.is_mid_instruction() || stepped.peek_byte_at_pc() != Some(0) {
+            stepped.step_cycle();
+        }
+        assert_eq!(
+            (
+                stepped.a,
+                stepped.x,
+                stepped.y,
+                *stepped.p.raw(),
+                stepped.s,
+                stepped.pc
+            ),
+            (whole.a, whole.x, whole.y, *whole.p.raw(), whole.s, whole.pc)
+        );
+        assert_eq!(stepped.cycles(), whole.cycles());
+        assert_eq!(
+            stepped.memory.read_bytes(0x0100, 0x1500),
+            whole.memory.read_bytes(0x0100, 0x1500)
+        );
+    }
+
+    #[test]
+    fn nop_is_one_byte() {
+        // NOP; LDA #$07
+        let processor = run_program(&[0xEA, 0xA9, 0x07]);
+        assert_eq!(processor.a, 0x07);
+    }
+    #[test]
+    fn loading_and_storing() {
+        let bin = include_bytes!("../tests/fixtures/loading_and_storing/test.bin");
+        let mut memory = Memory::new();
         memory.write_bytes(0x1000, bin);
-        let mut processor = Processor::new(memory);
-        // Using 0 byte for program termination for now (which corresponds to the BRK instruction)
-        while processor.peek_byte_at_pc() != 0 {
-            processor.process_next_instruction();
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+
+        assert_eq!(
+            processor.memory.read_byte(0x1000),
+            processor.memory.read_byte(0x100D)
+        );
+    }
+
+    #[test]
+    fn unsigned_addition() {
+        let bin = include_bytes!("../tests/fixtures/unsigned_addition/test.bin");
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, bin);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+
+        println!("{:#X?}", processor);
+        assert_eq!(
+            processor.memory.read_byte(0x1015) + processor.memory.read_byte(0x1016),
+            processor.memory.read_byte(0x1017)
+        )
+    }
+
+    #[test]
+    fn lax_loads_a_and_x() {
+        // LAX $20
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x80);
+        memory.write_bytes(0x1000, &[0xA7, 0x20]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.process_next_instruction().unwrap();
+        assert_eq!((processor.a, processor.x), (0x80, 0x80));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn sax_stores_a_and_x() {
+        // LDA #$F0; LDX #$3C; LDY #$01; SAX $20,Y
+        let processor = run_program(&[0xA9, 0xF0, 0xA2, 0x3C, 0xA0, 0x01, 0x97, 0x20]);
+        assert_eq!(processor.memory.read_byte(0x21), 0x30);
+    }
+
+    #[test]
+    fn dcp_decrements_and_compares() {
+        // LDA #$10; DCP $20
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x11);
+        memory.write_bytes(0x1000, &[0xA9, 0x10, 0xC7, 0x20]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.memory.read_byte(0x20), 0x10);
+        assert!(processor.p.get(StatusFlag::Zero));
+        assert!(processor.p.get(StatusFlag::Carry));
+    }
+
+    #[test]
+    fn isc_increments_and_subtracts() {
+        // SEC; LDA #$10; ISC $20
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x04);
+        memory.write_bytes(0x1000, &[0x38, 0xA9, 0x10, 0xE7, 0x20]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        for _ in 0..3 {
+            processor.process_next_instruction().unwrap();
+        }
+        assert_eq!(processor.memory.read_byte(0x20), 0x05);
+        assert_eq!(processor.a, 0x0B);
+        assert!(processor.p.get(StatusFlag::Carry));
+    }
+
+    #[test]
+    fn shift_and_rotate_combinations() {
+        // LDA #$01; SLO $20 ($81 -> $02, C set, A = $03)
+        let processor = {
+            let mut memory = Memory::new();
+            memory.write_byte(0x20, 0x81);
+            memory.write_bytes(0x1000, &[0xA9, 0x01, 0x07, 0x20]);
+            let mut processor = Processor::with_start_address(memory, 0x1000);
+            processor.process_next_instruction().unwrap();
+            processor.process_next_instruction().unwrap();
+            processor
+        };
+        assert_eq!(processor.memory.read_byte(0x20), 0x02);
+        assert_eq!(processor.a, 0x03);
+        assert!(processor.p.get(StatusFlag::Carry));
+
+        // SEC; LDA #$FF; RLA $20 ($40 -> $81, A = $81)
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x40);
+        memory.write_bytes(0x1000, &[0x38, 0xA9, 0xFF, 0x27, 0x20]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        for _ in 0..3 {
+            processor.process_next_instruction().unwrap();
+        }
+        assert_eq!(processor.a, 0x81);
+        assert!(!processor.p.get(StatusFlag::Carry));
+
+        // LDA #$0F; SRE $20 ($03 -> $01, C set, A = $0E)
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x03);
+        memory.write_bytes(0x1000, &[0xA9, 0x0F, 0x47, 0x20]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.a, 0x0E);
+        assert!(processor.p.get(StatusFlag::Carry));
+
+        // CLC; LDA #$10; RRA $20 ($03 -> $01 with C set, A = $10 + $01 + 1)
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x03);
+        memory.write_bytes(0x1000, &[0x18, 0xA9, 0x10, 0x67, 0x20]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        for _ in 0..3 {
+            processor.process_next_instruction().unwrap();
+        }
+        assert_eq!(processor.memory.read_byte(0x20), 0x01);
+        assert_eq!(processor.a, 0x12);
+    }
+
+    #[test]
+    fn anc_alr_and_sbx() {
+        // LDA #$F0; ANC #$80
+        let processor = run_program(&[0xA9, 0xF0, 0x0B, 0x80]);
+        assert_eq!(processor.a, 0x80);
+        assert!(processor.p.get(StatusFlag::Carry));
+        // LDA #$FF; ALR #$03
+        let processor = run_program(&[0xA9, 0xFF, 0x4B, 0x03]);
+        assert_eq!(processor.a, 0x01);
+        assert!(processor.p.get(StatusFlag::Carry));
+        // LDA #$0F; LDX #$FC; SBX #$0D
+        let processor = run_program(&[0xA9, 0x0F, 0xA2, 0xFC, 0xCB, 0x0D]);
+        assert_eq!(processor.x, 0xFF);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn arr_sets_carry_and_overflow_from_bits_6_and_5() {
+        // SEC; LDA #$FF; ARR #$C0
+        let processor = run_program(&[0x38, 0xA9, 0xFF, 0x6B, 0xC0]);
+        assert_eq!(processor.a, 0xE0);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(!processor.p.get(StatusFlag::Overflow));
+        // CLC; LDA #$FF; ARR #$40
+        let processor = run_program(&[0x18, 0xA9, 0xFF, 0x6B, 0x40]);
+        assert_eq!(processor.a, 0x20);
+        assert!(!processor.p.get(StatusFlag::Carry));
+        assert!(processor.p.get(StatusFlag::Overflow));
+    }
+
+    #[test]
+    fn arr_in_decimal_mode() {
+        // SED; CLC; LDA #$FF; ARR #$99
+        let processor = run_program(&[0xF8, 0x18, 0xA9, 0xFF, 0x6B, 0x99]);
+        // $99 >> 1 is $4C, then both digits are corrected: $42, then $A2
+        assert_eq!(processor.a, 0xA2);
+        assert!(processor.p.get(StatusFlag::Carry));
+        assert!(!processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn xaa_and_lxa_use_magic_constant() {
+        // LDA #$01; LDX #$FF; XAA #$F3
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0xA9, 0x01, 0xA2, 0xFF, 0x8B, 0xF3, 0xAB, 0x0F]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        assert_eq!(processor.magic_constant(), DEFAULT_MAGIC_CONSTANT);
+        for _ in 0..3 {
+            processor.process_next_instruction().unwrap();
         }
+        assert_eq!(processor.a, (0x01 | 0xEE) & 0xF3);
+        // LXA #$0F with a different constant
+        processor.set_magic_constant(0xFF);
+        processor.process_next_instruction().unwrap();
+        assert_eq!((processor.a, processor.x), (0x0F, 0x0F));
+    }
 
-        println!("{:#X?}", processor);
+    #[test]
+    fn las_and_tas_use_stack_pointer() {
+        // LDA #$F7; LDX #$3F; LDY #$00; TAS $1200,Y; LAS $1200,Y
+        let mut memory = Memory::new();
+        memory.write_bytes(
+            0x1000,
+            &[
+                0xA9, 0xF7, 0xA2, 0x3F, 0xA0, 0x00, 0x9B, 0x00, 0x12, 0xBB, 0x00, 0x12,
+            ],
+        );
+        memory.write_byte(0x1200, 0xFF);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        for _ in 0..4 {
+            processor.process_next_instruction().unwrap();
+        }
+        assert_eq!(processor.s, 0x37);
+        // Stored value is ANDed with the high byte plus one
+        assert_eq!(processor.memory.read_byte(0x1200), 0x37 & 0x13);
+        processor.process_next_instruction().unwrap();
+        assert_eq!((processor.a, processor.x, processor.s), (0x13, 0x13, 0x13));
+    }
+
+    #[test]
+    fn sha_shx_and_shy_store_and_high_byte() {
+        // LDA #$FF; LDX #$FF; LDY #$01; SHY $12FF,X
+        let processor = run_program(&[0xA9, 0xFF, 0xA2, 0x01, 0xA0, 0xFF, 0x9C, 0x00, 0x12]);
+        assert_eq!(processor.memory.read_byte(0x1201), 0x13);
+        // LDX #$FF; LDY #$10; SHX $1200,Y
+        let processor = run_program(&[0xA2, 0xFF, 0xA0, 0x10, 0x9E, 0x00, 0x12]);
+        assert_eq!(processor.memory.read_byte(0x1210), 0x13);
+        // LDA #$0F; LDX #$FF; LDY #$01; SHA $12FF,Y crosses a page, so the high byte is replaced
+        let processor = run_program(&[0xA9, 0x0F, 0xA2, 0xFF, 0xA0, 0x01, 0x9F, 0xFF, 0x12]);
+        assert_eq!(processor.memory.read_byte(0x0300), 0x03);
+        assert_eq!(processor.memory.read_byte(0x1300), 0x00);
+    }
+
+    #[test]
+    fn illegal_nops_read_their_operands() {
+        // NOP #$12; NOP $20; NOP $2000,X; NOP
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0x80, 0x12, 0x04, 0x20, 0x1C, 0x00, 0x20, 0x1A]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        let start = processor.cycles();
+        for _ in 0..4 {
+            processor.process_next_instruction().unwrap();
+        }
+        assert_eq!(processor.pc, 0x1008);
+        assert_eq!(processor.cycles() - start, 2 + 3 + 4 + 2);
+    }
+
+    #[test]
+    fn jam_locks_up_until_reset() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0x02]);
+        memory.write_bytes(RESET_VECTOR, &[0x00, 0x40]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        let jammed = Err(CpuError::Jammed {
+            pc: 0x1000,
+            bytes: vec![0x02],
+        });
+        assert_eq!(processor.process_next_instruction(), jammed);
+        assert!(processor.is_jammed());
+        let pc = processor.pc;
+        processor.trigger_nmi();
+        processor.set_irq_line(true);
+        for _ in 0..3 {
+            let start = processor.cycles();
+            assert_eq!(processor.process_next_instruction(), jammed);
+            assert_eq!(processor.cycles() - start, 1);
+            assert_eq!(
+                processor.step_cycle(),
+                read(0xFFFF, processor.memory.read_byte(0xFFFF))
+            );
+        }
+        assert_eq!(processor.pc, pc);
+        processor.reset();
+        assert!(!processor.is_jammed());
+        assert_eq!(processor.pc, 0x4000);
+    }
+
+    /// Runs `program` on a 65C02 from 0x1000 until it reaches a BRK
+    fn run_cmos_program(program: &[u8]) -> Processor {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, program);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        processor
+    }
+
+    #[test]
+    fn cmos_indexed_shifts_only_take_extra_cycle_across_pages() {
+        // LDX #$01; ASL $10FE,X; ASL $10FF,X; INC $10FE,X
+        let mut memory = Memory::new();
+        memory.write_bytes(
+            0x1000,
+            &[
+                0xA2, 0x01, 0x1E, 0xFE, 0x10, 0x1E, 0xFF, 0x10, 0xFE, 0xFE, 0x10,
+            ],
+        );
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.process_next_instruction().unwrap(), 6);
+        assert_eq!(processor.process_next_instruction().unwrap(), 7);
+        assert_eq!(processor.process_next_instruction().unwrap(), 7);
+    }
+
+    #[test]
+    fn cmos_read_modify_write_reads_twice() {
+        // INC $20
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x41);
+        memory.write_bytes(0x1000, &[0xE6, 0x20]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
         assert_eq!(
-            processor.memory.read_byte(0x1015) + processor.memory.read_byte(0x1016),
-            processor.memory.read_byte(0x1017)
-        )
+            step_instruction(&mut processor),
+            [
+                read(0x1000, 0xE6),
+                read(0x1001, 0x20),
+                read(0x0020, 0x41),
+                read(0x0020, 0x41),
+                write(0x0020, 0x42),
+            ]
+        );
+    }
+
+    #[test]
+    fn cmos_stack_instructions() {
+        // LDX #$12; LDY #$34; PHX; PHY; LDX #$00; LDY #$00; PLX; PLY
+        let processor = run_cmos_program(&[
+            0xA2, 0x12, 0xA0, 0x34, 0xDA, 0x5A, 0xA2, 0x00, 0xA0, 0x00, 0xFA, 0x7A,
+        ]);
+        assert_eq!((processor.x, processor.y), (0x34, 0x12));
+        assert_eq!(processor.s, 0xFD);
+    }
+
+    #[test]
+    fn bra_always_branches() {
+        // BRA +2; LDA #$01; LDA #$02
+        let processor = run_cmos_program(&[0x80, 0x02, 0xA9, 0x01, 0xA9, 0x02]);
+        assert_eq!(processor.a, 0x02);
+    }
+
+    #[test]
+    fn stz_stores_zero() {
+        // LDX #$01; STZ $20; STZ $20,X; STZ $1200; STZ $1200,X
+        let mut memory = Memory::new();
+        memory.write_bytes(0x20, &[0xFF, 0xFF]);
+        memory.write_bytes(0x1200, &[0xFF, 0xFF]);
+        memory.write_bytes(
+            0x1000,
+            &[
+                0xA2, 0x01, 0x64, 0x20, 0x74, 0x20, 0x9C, 0x00, 0x12, 0x9E, 0x00, 0x12,
+            ],
+        );
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        for _ in 0..5 {
+            processor.process_next_instruction().unwrap();
+        }
+        assert_eq!(processor.memory.read_bytes(0x20, 2), [0, 0]);
+        assert_eq!(processor.memory.read_bytes(0x1200, 2), [0, 0]);
+    }
+
+    #[test]
+    fn trb_and_tsb() {
+        // LDA #$0F; TSB $20; TRB $21
+        let mut memory = Memory::new();
+        memory.write_bytes(0x20, &[0xF0, 0xFF]);
+        memory.write_bytes(0x1000, &[0xA9, 0x0F, 0x04, 0x20, 0x14, 0x21]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.memory.read_byte(0x20), 0xFF);
+        assert!(processor.p.get(StatusFlag::Zero));
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.memory.read_byte(0x21), 0xF0);
+        assert!(!processor.p.get(StatusFlag::Zero));
+    }
+
+    #[test]
+    fn increment_and_decrement_accumulator() {
+        // LDA #$FF; INC A; INC A; DEC A; DEC A
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0xA9, 0xFF, 0x1A, 0x1A, 0x3A, 0x3A]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.a, 0x00);
+        assert!(processor.p.get(StatusFlag::Zero));
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.a, 0xFF);
+        assert!(processor.p.get(StatusFlag::Negative));
+    }
+
+    #[test]
+    fn zero_page_indirect() {
+        // LDA ($20); STA ($22)
+        let mut memory = Memory::new();
+        memory.write_bytes(0x20, &[0x00, 0x12, 0x00, 0x13]);
+        memory.write_byte(0x1200, 0x5A);
+        memory.write_bytes(0x1000, &[0xB2, 0x20, 0x92, 0x22]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.memory.read_byte(0x1300), 0x5A);
+    }
+
+    #[test]
+    fn bit_immediate_only_sets_zero() {
+        // CLV; LDA #$01; BIT #$C0
+        let processor = run_cmos_program(&[0xB8, 0xA9, 0x01, 0x89, 0xC0]);
+        assert!(processor.p.get(StatusFlag::Zero));
+        assert!(!processor.p.get(StatusFlag::Negative));
+        assert!(!processor.p.get(StatusFlag::Overflow));
+    }
+
+    #[test]
+    fn rockwell_bit_instructions() {
+        // SMB3 $20; RMB0 $20; BBS3 $20,+2; LDA #$01; BBR0 $20,+2; LDX #$01; BBS0 $20,+2; LDY #$01
+        let processor = run_cmos_program(&[
+            0xB7, 0x20, 0x07, 0x20, 0xBF, 0x20, 0x02, 0xA9, 0x01, 0x0F, 0x20, 0x02, 0xA2, 0x01,
+            0x8F, 0x20, 0x02, 0xA0, 0x01,
+        ]);
+        assert_eq!(processor.memory.read_byte(0x20), 0x08);
+        assert_eq!((processor.a, processor.x, processor.y), (0x00, 0x00, 0x01));
+    }
+
+    #[test]
+    fn jmp_indirect_is_fixed_on_cmos() {
+        // JMP ($10FF)
+        let mut memory = Memory::new();
+        memory.write_bytes(0x10FF, &[0x34, 0x12]);
+        memory.write_byte(0x1000, 0x56);
+        memory.write_bytes(0x2000, &[0x6C, 0xFF, 0x10]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x2000;
+        assert_eq!(processor.process_next_instruction().unwrap(), 6);
+        assert_eq!(processor.pc, 0x1234);
+    }
+
+    #[test]
+    fn jmp_absolute_indexed_indirect() {
+        // LDX #$02; JMP ($2000,X)
+        let mut memory = Memory::new();
+        memory.write_bytes(0x2002, &[0x34, 0x12]);
+        memory.write_bytes(0x1000, &[0xA2, 0x02, 0x7C, 0x00, 0x20]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1234);
+    }
+
+    #[test]
+    fn cmos_clears_decimal_on_interrupt() {
+        // SED; BRK
+        let mut memory = Memory::new();
+        memory.write_bytes(IRQ_VECTOR, &[0x00, 0x20]);
+        memory.write_bytes(0x1000, &[0xF8, 0x00]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x2000);
+        assert!(!processor.p.get(StatusFlag::Decimal));
+        // The pushed status still has it set
+        assert_ne!(processor.memory.read_byte(0x01FB) & 0b00001000, 0);
+    }
+
+    #[test]
+    fn cmos_decimal_flags_are_valid() {
+        // SED; CLC; LDA #$99; ADC #$01
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0xF8, 0x18, 0xA9, 0x99, 0x69, 0x01]);
+        let mut processor = Processor::with_variant(memory, Variant::Wdc65C02);
+        processor.pc = 0x1000;
+        for _ in 0..3 {
+            processor.process_next_instruction().unwrap();
+        }
+        // Takes an extra cycle in decimal mode
+        assert_eq!(processor.process_next_instruction().unwrap(), 3);
+        assert_eq!(processor.a, 0x00);
+        assert!(processor.p.get(StatusFlag::Zero));
+        assert!(!processor.p.get(StatusFlag::Negative));
+        assert!(processor.p.get(StatusFlag::Carry));
+    }
+
+    fn cmos_processor_with_handlers(program: &[u8]) -> Processor {
+        let mut processor = processor_with_handlers(program, &[0x40], &[0x40]);
+        processor.variant = Variant::Wdc65C02;
+        processor
+    }
+
+    #[test]
+    fn wai_waits_for_interrupt() {
+        let mut processor = cmos_processor_with_handlers(&[0xCB, 0xE8]);
+        processor.process_next_instruction().unwrap();
+        assert!(processor.is_waiting());
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1001);
+        processor.set_irq_line(true);
+        processor.process_next_instruction().unwrap();
+        assert!(!processor.is_waiting());
+        assert_eq!(processor.pc, 0x2000);
+    }
+
+    #[test]
+    fn wai_resumes_without_servicing_masked_irq() {
+        // SEI; WAI; INX
+        let mut processor = cmos_processor_with_handlers(&[0x78, 0xCB, 0xE8]);
+        processor.process_next_instruction().unwrap();
+        processor.process_next_instruction().unwrap();
+        processor.set_irq_line(true);
+        processor.process_next_instruction().unwrap();
+        assert_eq!(processor.pc, 0x1003);
+        assert_eq!(processor.x, 1);
+    }
+
+    #[test]
+    fn stp_stops_until_reset() {
+        let mut processor = cmos_processor_with_handlers(&[0xDB]);
+        let stopped = Err(CpuError::Jammed {
+            pc: 0x1000,
+            bytes: vec![0xDB],
+        });
+        assert_eq!(processor.process_next_instruction(), stopped);
+        processor.trigger_nmi();
+        assert_eq!(processor.process_next_instruction(), stopped);
+        assert!(processor.is_jammed());
+        processor.reset();
+        assert_eq!(processor.pc, 0x4000);
+    }
+
+    /// Creates a processor of the given variant about to run `program` from 0x1000
+    fn processor_for(variant: Variant, program: &[u8]) -> Processor {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, program);
+        let mut processor = Processor::with_variant(memory, variant);
+        processor.pc = 0x1000;
+        processor
+    }
+
+    #[test]
+    fn variants_agree_on_documented_programs() {
+        // LDX #$05; LDA #$00; CLC; loop: ADC #$07; DEX; BNE loop; STA $20; ROL $20; PHA; PLA
+        let program = [
+            0xA2, 0x05, 0xA9, 0x00, 0x18, 0x69, 0x07, 0xCA, 0xD0, 0xFB, 0x85, 0x20, 0x26, 0x20,
+            0x48, 0x68,
+        ];
+        for variant in Variant::ALL {
+            let mut processor = processor_for(variant, &program);
+            assert_eq!(processor.run_until_brk(), StopReason::Brk);
+            assert_eq!(processor.variant(), variant);
+            assert_eq!(processor.a, 35, "{:?}", variant);
+            assert_eq!(processor.memory.read_byte(0x20), 70, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn ricoh_2a03_has_no_decimal_mode() {
+        // SED; CLC; LDA #$09; ADC #$01
+        let program = [0xF8, 0x18, 0xA9, 0x09, 0x69, 0x01];
+        for variant in Variant::ALL {
+            let mut processor = processor_for(variant, &program);
+            for _ in 0..4 {
+                processor.process_next_instruction().unwrap();
+            }
+            let expected = if variant == Variant::Ricoh2A03 {
+                0x0A
+            } else {
+                0x10
+            };
+            assert_eq!(processor.a, expected, "{:?}", variant);
+            // The flag itself still works
+            assert!(processor.p.get(StatusFlag::Decimal));
+        }
+    }
+
+    #[test]
+    fn undocumented_opcodes_depend_on_variant() {
+        // LDA #$0F; ANC #$F0, which is a single byte NOP on the 65C02
+        let program = [0xA9, 0x0F, 0x0B, 0xF0];
+        for variant in Variant::ALL {
+            let mut processor = processor_for(variant, &program);
+            processor.process_next_instruction().unwrap();
+            processor.process_next_instruction().unwrap();
+            if variant.has_undocumented_opcodes() {
+                assert_eq!((processor.a, processor.pc), (0x00, 0x1004), "{:?}", variant);
+            } else {
+                assert_eq!((processor.a, processor.pc), (0x0F, 0x1003), "{:?}", variant);
+            }
+        }
+    }
+
+    #[test]
+    fn indirect_jump_bug_depends_on_variant() {
+        for variant in Variant::ALL {
+            let mut processor = processor_for(variant, &[0x6C, 0xFF, 0x12]);
+            processor.memory.write_bytes(0x12FF, &[0x34, 0x56]);
+            processor.memory.write_byte(0x1200, 0x78);
+            processor.process_next_instruction().unwrap();
+            let expected = if variant.has_indirect_jump_bug() {
+                0x7834
+            } else {
+                0x5634
+            };
+            assert_eq!(processor.pc, expected, "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn mos_6510_has_io_port() {
+        // LDA #$07; STA $00; LDA #$05; STA $01; LDA $01
+        let program = [0xA9, 0x07, 0x85, 0x00, 0xA9, 0x05, 0x85, 0x01, 0xA5, 0x01];
+        let mut memory = Memory::new();
+        memory.attach_io_port(IoPort::c64());
+        memory.write_bytes(0x1000, &program);
+        let mut processor = Processor::with_variant(memory, Variant::Mos6510);
+        processor.pc = 0x1000;
+        assert_eq!(processor.memory.read_byte(0x01), 0b00010111);
+        for _ in 0..5 {
+            processor.process_next_instruction().unwrap();
+        }
+        // The cassette sense bit is still an input, pulled up
+        assert_eq!(processor.a, 0b00010101);
+        assert_eq!(processor.memory.io_port().unwrap().pins(), 0b00010101);
+        assert!(
+            Processor::with_variant(Memory::new(), Variant::Nmos6502)
+                .memory
+                .io_port()
+                .is_none()
+        );
+        assert!(
+            Processor::with_variant(Memory::new(), Variant::Mos6510)
+                .memory
+                .io_port()
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn undefined_opcodes_execute_by_default() {
+        // SLO $20
+        let mut processor = processor_for(Variant::Nmos6502, &[0x07, 0x20]);
+        processor.memory.write_byte(0x20, 0x40);
+        assert_eq!(processor.process_next_instruction(), Ok(5));
+        assert_eq!(processor.memory.read_byte(0x20), 0x80);
+        assert_eq!(processor.a, 0x80);
+    }
+
+    #[test]
+    fn halt_policy_stops_on_undefined_opcodes() {
+        // LDA #$01; SLO $20
+        let mut processor = processor_for(Variant::Nmos6502, &[0xA9, 0x01, 0x07, 0x20]);
+        processor.set_undefined_opcode_policy(UndefinedOpcodePolicy::Halt);
+        assert_eq!(processor.process_next_instruction(), Ok(2));
+        let halted = Err(CpuError::UnknownOpcode {
+            pc: 0x1002,
+            bytes: vec![0x07, 0x20],
+        });
+        assert_eq!(processor.process_next_instruction(), halted);
+        assert_eq!(processor.pc, 0x1002);
+        assert_eq!(processor.process_next_instruction(), halted);
+    }
+
+    #[test]
+    fn nop_policy_skips_whole_instruction() {
+        for (variant, program) in [
+            // SLO $2000
+            (Variant::Nmos6502, [0x0F, 0x00, 0x20]),
+            // The 65C02's three byte filler NOP
+            (Variant::Wdc65C02, [0xDC, 0x00, 0x20]),
+        ] {
+            let mut processor = processor_for(variant, &program);
+            processor.memory.write_byte(0x2000, 0x40);
+            processor.set_undefined_opcode_policy(UndefinedOpcodePolicy::Nop);
+            assert_eq!(processor.process_next_instruction(), Ok(3));
+            assert_eq!(processor.pc, 0x1003);
+            assert_eq!(processor.memory.read_byte(0x2000), 0x40);
+        }
+    }
+
+    #[test]
+    fn trap_policy_calls_handler_once() {
+        fn count_traps(processor: &mut Processor, pc: u16, opcode: u8) {
+            assert_eq!((pc, opcode), (0x1000, 0x0C));
+            assert_eq!(processor.pc, 0x1003);
+            *processor.memory.mut_byte(0x0200) += 1;
+        }
+        // NOP $2000 (undocumented)
+        let mut processor = processor_for(Variant::Nmos6502, &[0x0C, 0x00, 0x20]);
+        processor.set_undefined_opcode_policy(UndefinedOpcodePolicy::Trap(count_traps));
+        assert_eq!(processor.process_next_instruction(), Ok(3));
+        assert_eq!(processor.memory.read_byte(0x0200), 1);
+        // Stepping through it still only traps once
+        processor.pc = 0x1000;
+        for _ in 0..3 {
+            processor.step_cycle();
+        }
+        assert!(!processor.is_mid_instruction());
+        assert_eq!(processor.memory.read_byte(0x0200), 2);
+    }
+
+    #[test]
+    fn jam_policy_locks_up_cmos() {
+        let mut processor = processor_for(Variant::Wdc65C02, &[0x02, 0x00]);
+        processor.set_undefined_opcode_policy(UndefinedOpcodePolicy::Jam);
+        assert_eq!(
+            processor.process_next_instruction(),
+            Err(CpuError::Jammed {
+                pc: 0x1000,
+                bytes: vec![0x02, 0x00],
+            })
+        );
+        assert!(processor.is_jammed());
+    }
+
+    #[test]
+    fn policy_leaves_defined_opcodes_alone() {
+        // PHX; BRA +0
+        let mut processor = processor_for(Variant::Wdc65C02, &[0xDA, 0x80, 0x00]);
+        processor.set_undefined_opcode_policy(UndefinedOpcodePolicy::Halt);
+        assert_eq!(processor.process_next_instruction(), Ok(3));
+        assert_eq!(processor.process_next_instruction(), Ok(3));
+        assert_eq!(processor.pc, 0x1003);
+    }
+
+    /// Records every event it's given, as (before, after) pairs
+    #[derive(Default)]
+    struct RecordingTracer(Rc<RefCell<Vec<(TraceEvent, bool)>>>);
+
+    impl Tracer for RecordingTracer {
+        fn before_instruction(&mut self, event: &TraceEvent) {
+            self.0.borrow_mut().push((*event, false));
+        }
+        fn after_instruction(&mut self, event: &TraceEvent) {
+            self.0.borrow_mut().push((*event, true));
+        }
+    }
+
+    #[test]
+    fn tracer_sees_each_instruction() {
+        // LDA #$05; STA $0200; INX; JSR $1010
+        let program = [0xA9, 0x05, 0x8D, 0x00, 0x02, 0xE8, 0x20, 0x10, 0x10];
+        let mut processor = processor_for(Variant::Nmos6502, &program);
+        let tracer = RecordingTracer::default();
+        let events = tracer.0.clone();
+        processor.set_tracer(Some(Box::new(tracer)));
+        for _ in 0..4 {
+            processor.process_next_instruction().unwrap();
+        }
+        let events = events.borrow();
+        assert_eq!(events.len(), 8);
+        let (before, _) = events[0];
+        let (after, is_after) = events[1];
+        assert!(is_after);
+        assert_eq!(
+            (before.pc, before.opcode, before.addressing_mode),
+            (0x1000, 0xA9, AddressingMode::Immediate)
+        );
+        assert_eq!((before.state.a, after.state.a), (0x00, 0x05));
+        assert_eq!((before.state.pc, after.state.pc), (0x1000, 0x1002));
+        let addresses: Vec<_> = events
+            .iter()
+            .map(|(event, _)| event.effective_address)
+            .collect();
+        assert_eq!(
+            addresses,
+            [
+                None,
+                Some(0x1001),
+                None,
+                Some(0x0200),
+                None,
+                None,
+                None,
+                Some(0x1010)
+            ]
+        );
+    }
+
+    #[test]
+    fn tracer_is_called_once_when_stepping() {
+        // STA $0200
+        let mut processor = processor_for(Variant::Nmos6502, &[0x8D, 0x00, 0x02]);
+        let tracer = RecordingTracer::default();
+        let events = tracer.0.clone();
+        processor.set_tracer(Some(Box::new(tracer)));
+        for _ in 0..4 {
+            processor.step_cycle();
+        }
+        assert!(!processor.is_mid_instruction());
+        assert_eq!(events.borrow().len(), 2);
+        assert!(processor.set_tracer(None).is_some());
+    }
+
+    /// Flat RAM that can be shared with a tracer
+    struct SharedBus(Rc<RefCell<Vec<u8>>>);
+
+    impl Bus for SharedBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            self.0.borrow()[addr as usize]
+        }
+        fn write(&mut self, addr: u16, byte: u8) {
+            self.0.borrow_mut()[addr as usize] = byte;
+        }
+    }
+
+    /// Records the byte at $0200 when each instruction starts
+    struct PeekingTracer {
+        ram: Rc<RefCell<Vec<u8>>>,
+        seen: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Tracer for PeekingTracer {
+        fn before_instruction(&mut self, _event: &TraceEvent) {
+            self.seen.borrow_mut().push(self.ram.borrow()[0x0200]);
+        }
+    }
+
+    #[test]
+    fn tracer_sees_memory_before_instruction() {
+        let ram = Rc::new(RefCell::new(vec![0; 0x10000]));
+        // LDA #$05; STA $0200; INC $0200
+        ram.borrow_mut()[0x1000..0x1008]
+            .copy_from_slice(&[0xA9, 0x05, 0x8D, 0x00, 0x02, 0xEE, 0x00, 0x02]);
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut processor = Processor::with_start_address(SharedBus(ram.clone()), 0x1000);
+        processor.set_tracer(Some(Box::new(PeekingTracer {
+            ram: ram.clone(),
+            seen: seen.clone(),
+        })));
+        processor.step(2);
+        // Stepping a cycle at a time calls it on the first cycle, before INC writes
+        for _ in 0..6 {
+            processor.step_cycle();
+        }
+        assert_eq!(*seen.borrow(), [0x00, 0x00, 0x05]);
+        assert_eq!(ram.borrow()[0x0200], 0x06);
+    }
+
+    #[test]
+    fn step_runs_instructions() {
+        // LDX #$03; loop: DEX; BNE loop
+        let mut processor = processor_for(Variant::Nmos6502, &[0xA2, 0x03, 0xCA, 0xD0, 0xFD]);
+        assert_eq!(processor.step(3), StopReason::StepsCompleted);
+        assert_eq!((processor.x, processor.pc), (0x02, 0x1002));
+        assert_eq!(processor.step(0), StopReason::StepsCompleted);
+        assert_eq!(processor.pc, 0x1002);
+    }
+
+    #[test]
+    fn run_for_cycles_finishes_last_instruction() {
+        // NOP; NOP; LDA $2000; NOP
+        let program = [0xEA, 0xEA, 0xAD, 0x00, 0x20, 0xEA];
+        let mut processor = processor_for(Variant::Nmos6502, &program);
+        let start = processor.cycles();
+        assert_eq!(processor.run_for_cycles(5), StopReason::CyclesElapsed);
+        assert_eq!(processor.cycles() - start, 8);
+        assert_eq!(processor.pc, 0x1005);
+    }
+
+    #[test]
+    fn run_until_checks_predicate_first() {
+        // INX; INX; INX
+        let mut processor = processor_for(Variant::Nmos6502, &[0xE8, 0xE8, 0xE8]);
+        assert_eq!(processor.run_until(|_| true), StopReason::Predicate);
+        assert_eq!(processor.pc, 0x1000);
+        assert_eq!(
+            processor.run_until(|processor| processor.x == 2),
+            StopReason::Predicate
+        );
+        assert_eq!(processor.pc, 0x1002);
+    }
+
+    #[test]
+    fn breakpoints_stop_runs_and_can_be_resumed() {
+        // INX; INX; INX
+        let mut processor = processor_for(Variant::Nmos6502, &[0xE8, 0xE8, 0xE8]);
+        processor.add_breakpoint(0x1001);
+        assert_eq!(processor.run_until_brk(), StopReason::Breakpoint(0x1001));
+        assert_eq!(processor.x, 1);
+        // Carries on past the breakpoint it stopped at
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.x, 3);
+        assert!(processor.remove_breakpoint(0x1001));
+        assert!(!processor.remove_breakpoint(0x1001));
+    }
+
+    #[test]
+    fn runs_stop_when_halted() {
+        // INX; JAM
+        let mut processor = processor_for(Variant::Nmos6502, &[0xE8, 0x02]);
+        assert_eq!(
+            processor.step(5),
+            StopReason::Halted(CpuError::Jammed {
+                pc: 0x1001,
+                bytes: vec![0x02],
+            })
+        );
+        assert_eq!(processor.x, 1);
+    }
+
+    #[test]
+    fn registers_can_be_set_and_read() {
+        // STA $20; TXA
+        let mut processor = processor_for(Variant::Nmos6502, &[0x85, 0x20, 0x8A]);
+        processor.set_a(0x12);
+        processor.set_x(0x34);
+        processor.step(1);
+        assert_eq!(processor.memory.read_byte(0x20), 0x12);
+        let mut state = processor.state();
+        assert_eq!(state.pc, 0x1002);
+        processor.step(1);
+        assert_eq!(processor.a(), 0x34);
+        // Putting the state back runs TXA again from the start
+        state.x = 0x56;
+        processor.set_state(state);
+        assert_eq!(processor.state(), state);
+        processor.step(1);
+        assert_eq!((processor.a(), processor.pc()), (0x56, 0x1003));
+    }
+
+    #[test]
+    fn rom_ignores_writes() {
+        // STA $C000; INC $C001
+        let mut processor = processor_for(Variant::Nmos6502, &[0x8D, 0x00, 0xC0, 0xEE, 0x01, 0xC0]);
+        processor.memory.write_byte(0xC001, 0x10);
+        processor.memory.mark_rom(0xC000..=0xFFFF);
+        processor.set_a(0x42);
+        assert_eq!(processor.step(2), StopReason::StepsCompleted);
+        assert_eq!(processor.memory.read_byte(0xC000), 0);
+        assert_eq!(processor.memory.read_byte(0xC001), 0x10);
+    }
+
+    #[test]
+    fn strict_rom_reports_writes() {
+        // LDA #$42; STA $C000; INC $C001; BRK
+        let program = [0xA9, 0x42, 0x8D, 0x00, 0xC0, 0xEE, 0x01, 0xC0, 0x00];
+        let mut processor = processor_for(Variant::Nmos6502, &program);
+        processor.memory.mark_rom(0xC000..=0xFFFF);
+        processor.set_strict_rom(true);
+        let error = CpuError::RomWrite {
+            pc: 0x1002,
+            bytes: vec![0x8D, 0x00, 0xC0],
+            address: 0xC000,
+            value: 0x42,
+        };
+        assert_eq!(processor.run_until_brk(), StopReason::Halted(error));
+        assert_eq!(processor.pc(), 0x1005);
+        // INC writes twice, but only the first write is reported
+        assert_eq!(
+            processor.process_next_instruction(),
+            Err(CpuError::RomWrite {
+                pc: 0x1005,
+                bytes: vec![0xEE, 0x01, 0xC0],
+                address: 0xC001,
+                value: 0x00,
+            })
+        );
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+    }
+
+    #[test]
+    fn strict_rom_reports_stepped_writes() {
+        // STA $C000
+        let mut processor = processor_for(Variant::Nmos6502, &[0x8D, 0x00, 0xC0]);
+        processor.memory.mark_rom(0xC000..=0xFFFF);
+        processor.set_strict_rom(true);
+        processor.set_a(0x42);
+        // Stop just before the write
+        for _ in 0..3 {
+            processor.step_cycle();
+        }
+        assert!(processor.is_mid_instruction());
+        assert_eq!(
+            processor.process_next_instruction(),
+            Err(CpuError::RomWrite {
+                pc: 0x1000,
+                bytes: vec![0x8D, 0x00, 0xC0],
+                address: 0xC000,
+                value: 0x42,
+            })
+        );
+        assert_eq!(processor.memory.read_byte(0xC000), 0);
+    }
+
+    #[test]
+    fn strict_rom_allows_mapper_registers() {
+        let mut memory = Memory::new();
+        memory.attach_mapper(UxRom::new(vec![0; 0x10000]));
+        memory.mark_rom(0x8000..=0xFFFF);
+        // LDA #$01; STA $8000
+        memory.write_bytes(0x1000, &[0xA9, 0x01, 0x8D, 0x00, 0x80]);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.set_strict_rom(true);
+        assert_eq!(processor.step(2), StopReason::StepsCompleted);
+        assert_eq!(processor.memory.mapper::<UxRom>().unwrap().bank(), 1);
+    }
+
+    #[test]
+    fn strict_rom_reports_writes_to_mapped_rom() {
+        let mut mapper = WindowMapper::new();
+        mapper.add_window(
+            BankedWindow::new(0x8000, BankSize::Size8K, vec![0; 0x4000], false),
+            0x5000,
+        );
+        mapper.add_window(
+            BankedWindow::new(0x6000, BankSize::Size8K, vec![0; 0x2000], true),
+            0x5001,
+        );
+        let mut memory = Memory::new();
+        memory.attach_mapper(mapper);
+        // STA $5000; STA $6000; STA $8000
+        memory.write_bytes(
+            0x1000,
+            &[0x8D, 0x00, 0x50, 0x8D, 0x00, 0x60, 0x8D, 0x00, 0x80],
+        );
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.set_strict_rom(true);
+        processor.set_a(1);
+        assert_eq!(processor.step(2), StopReason::StepsCompleted);
+        assert_eq!(
+            processor.process_next_instruction(),
+            Err(CpuError::RomWrite {
+                pc: 0x1006,
+                bytes: vec![0x8D, 0x00, 0x80],
+                address: 0x8000,
+                value: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn mirrored_ram() {
+        let mut memory = Memory::new();
+        memory.mirror(0x0000..=0x1FFF, 0x0800);
+        // LDA #$42; STA $0805; LDX $1805
+        memory.write_bytes(0x8000, &[0xA9, 0x42, 0x8D, 0x05, 0x08, 0xAE, 0x05, 0x18]);
+        let mut processor = Processor::with_start_address(memory, 0x8000);
+        processor.step(3);
+        assert_eq!(processor.x(), 0x42);
+        assert_eq!(processor.memory.read_byte(0x0005), 0x42);
+        assert_eq!(processor.memory.resolve(0x1FFF), 0x07FF);
+        assert_eq!(processor.memory.resolve(0x2000), 0x2000);
+    }
+
+    #[test]
+    fn unmapped_reads_open_bus() {
+        // LDA #$42; STA $5000; LDA $5000; LDX ($20),Y
+        let program = [0xA9, 0x42, 0x8D, 0x00, 0x50, 0xAD, 0x00, 0x50, 0xB1, 0x20];
+        let mut processor = processor_for(Variant::Nmos6502, &program);
+        processor.memory.mark_unmapped(0x4020..=0x5FFF);
+        processor.memory.write_bytes(0x20, &[0x00, 0x50]);
+        processor.step(3);
+        // The write was dropped, and the last thing on the bus was the high byte of the address
+        assert_eq!(processor.a(), 0x50);
+        assert_eq!(processor.memory.bus_value(), 0x50);
+        // Here it's the high byte of the pointer, read from zero page
+        processor.set_a(0);
+        processor.set_y(0x10);
+        processor.step(1);
+        assert_eq!(processor.a(), 0x50);
+    }
+
+    /// A bus with a register at $D000 that counts how often it's read, and can't be peeked
+    struct CountingBus {
+        ram: Vec<u8>,
+        reads_of_register: u8,
+    }
+
+    impl Bus for CountingBus {
+        fn read(&mut self, addr: u16) -> u8 {
+            if addr == 0xD000 {
+                self.reads_of_register += 1;
+                return self.reads_of_register;
+            }
+            self.ram[addr as usize]
+        }
+        fn write(&mut self, addr: u16, byte: u8) {
+            self.ram[addr as usize] = byte;
+        }
+    }
+
+    #[test]
+    fn runs_on_any_bus() {
+        let mut ram = vec![0; 0x10000];
+        // LDA $D000; LDA $D000; STA $20; JAM
+        ram[0x1000..0x1009]
+            .copy_from_slice(&[0xAD, 0x00, 0xD0, 0xAD, 0x00, 0xD0, 0x85, 0x20, 0x02]);
+        let bus = CountingBus {
+            ram,
+            reads_of_register: 0,
+        };
+        let mut processor = Processor::with_start_address(bus, 0x1000);
+        assert_eq!(processor.peek_byte_at_pc(), None);
+        // Without peeking, the error can't include the instruction's bytes
+        assert_eq!(
+            processor.step(4),
+            StopReason::Halted(CpuError::Jammed {
+                pc: 0x1008,
+                bytes: Vec::new(),
+            })
+        );
+        assert_eq!(processor.memory.ram[0x20], 2);
     }
 }
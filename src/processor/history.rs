@@ -0,0 +1,380 @@
+// Undo records for stepping backwards. Each instruction (or interrupt sequence) gets a record
+// holding the processor state from before it ran, and what the bus had behind every address it
+// wrote
+
+use std::collections::VecDeque;
+
+use super::Processor;
+use crate::bus::Bus;
+
+/// Everything about the processor an instruction can change, apart from memory
+#[derive(Clone, Copy, Debug)]
+struct Snapshot {
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    s: u8,
+    pc: u16,
+    irq_line: bool,
+    nmi_line: bool,
+    nmi_pending: bool,
+    irq_disabled_at_poll: bool,
+    cycles: u64,
+    jammed: Option<u16>,
+    waiting: bool,
+    bus_value: Option<u8>,
+}
+
+#[derive(Debug)]
+struct UndoRecord {
+    before: Snapshot,
+    // Address of each write, with what the bus captured before it, in the order they were made
+    writes: Vec<(u16, Vec<u8>)>,
+}
+
+/// Bounded history of undo records, oldest first
+#[derive(Debug)]
+pub(super) struct History {
+    capacity: usize,
+    records: VecDeque<UndoRecord>,
+    // Record for the instruction currently being run
+    current: Option<UndoRecord>,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        History {
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+            current: None,
+        }
+    }
+    /// An empty history with the same capacity
+    pub(super) fn cleared(&self) -> Self {
+        History::new(self.capacity)
+    }
+}
+
+impl<B: Bus> Processor<B> {
+    /// Starts keeping undo records for the last `capacity` instructions, so that they can be
+    /// stepped back through with [`Processor::step_back`]. Any existing history is dropped.
+    ///
+    /// Writes are only recorded when made over the bus by the processor, not when made to
+    /// `memory` directly. What each write could change is captured with [`Bus::save_write`],
+    /// and put back with [`Bus::restore_write`] when stepping back, so no side effects are
+    /// replayed. Writes the bus can't capture aren't undone
+    pub fn enable_history(&mut self, capacity: usize) {
+        self.history = Some(History::new(capacity));
+    }
+    pub fn disable_history(&mut self) {
+        self.history = None;
+    }
+    /// Number of instructions that can currently be stepped back through
+    pub fn history_len(&self) -> usize {
+        self.history
+            .as_ref()
+            .map_or(0, |history| history.records.len())
+    }
+    /// Undoes the last instruction, returning `false` if there's no history left.
+    /// If an instruction has been partly run by [`Processor::step_cycle`], that instruction is
+    /// undone instead, going back to its start
+    pub fn step_back(&mut self) -> bool {
+        let Some(history) = &mut self.history else {
+            return false;
+        };
+        let record = match history.current.take() {
+            Some(record) => record,
+            None => match history.records.pop_back() {
+                Some(record) => record,
+                None => return false,
+            },
+        };
+        self.undo(record);
+        true
+    }
+    /// Steps back until no more than `cycle` cycles have been run, landing on the start of the
+    /// instruction running at that cycle. Returns `false`, having gone back as far as it can,
+    /// if the history doesn't reach that far
+    pub fn rewind_to_cycle(&mut self, cycle: u64) -> bool {
+        while self.cycles > cycle {
+            if !self.step_back() {
+                return false;
+            }
+        }
+        true
+    }
+    /// Opens an undo record for the instruction about to be run
+    pub(super) fn begin_undo_record(&mut self) {
+        let before = self.snapshot();
+        if let Some(history) = &mut self.history {
+            history.current = Some(UndoRecord {
+                before,
+                writes: Vec::new(),
+            });
+        }
+    }
+    /// Closes the undo record for the instruction that has just finished
+    pub(super) fn finish_undo_record(&mut self) {
+        if let Some(history) = &mut self.history
+            && let Some(record) = history.current.take()
+        {
+            if history.capacity == 0 {
+                return;
+            }
+            if history.records.len() == history.capacity {
+                history.records.pop_front();
+            }
+            history.records.push_back(record);
+        }
+    }
+    /// Remembers what is about to be overwritten by a write to `addr`
+    pub(super) fn record_write(&mut self, addr: u16) {
+        if let Some(history) = &mut self.history
+            && let Some(record) = &mut history.current
+            && let Some(state) = self.memory.save_write(addr)
+        {
+            record.writes.push((addr, state));
+        }
+    }
+    fn undo(&mut self, record: UndoRecord) {
+        for (addr, state) in record.writes.iter().rev() {
+            self.memory.restore_write(*addr, state);
+        }
+        self.restore_snapshot(record.before);
+        self.cycle_step = None;
+    }
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            p: *self.p.raw(),
+            s: self.s,
+            pc: self.pc,
+            irq_line: self.irq_line,
+            nmi_line: self.nmi_line,
+            nmi_pending: self.nmi_pending,
+            irq_disabled_at_poll: self.irq_disabled_at_poll,
+            cycles: self.cycles,
+            jammed: self.jammed,
+            waiting: self.waiting,
+            bus_value: self.memory.open_bus_value(),
+        }
+    }
+    fn restore_snapshot(&mut self, snapshot: Snapshot) {
+        self.a = snapshot.a;
+        self.x = snapshot.x;
+        self.y = snapshot.y;
+        *self.p.raw_mut() = snapshot.p;
+        self.s = snapshot.s;
+        self.pc = snapshot.pc;
+        self.irq_line = snapshot.irq_line;
+        self.nmi_line = snapshot.nmi_line;
+        self.nmi_pending = snapshot.nmi_pending;
+        self.irq_disabled_at_poll = snapshot.irq_disabled_at_poll;
+        self.cycles = snapshot.cycles;
+        self.jammed = snapshot.jammed;
+        self.waiting = snapshot.waiting;
+        if let Some(value) = snapshot.bus_value {
+            self.memory.drive(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        banking::{Mmc1, UxRom},
+        io_port::IoPort,
+        memory::Memory,
+        processor::StopReason,
+        variant::Variant,
+    };
+
+    use super::*;
+
+    /// Stores 1 to 4 in $20 to $23, then BRKs
+    fn storing_processor() -> Processor {
+        let mut memory = Memory::new();
+        // LDX #$00; loop: INX; TXA; STA $1F,X; CPX #$04; BNE loop
+        memory.write_bytes(
+            0x1000,
+            &[0xA2, 0x00, 0xE8, 0x8A, 0x95, 0x1F, 0xE0, 0x04, 0xD0, 0xF8],
+        );
+        let mut processor = Processor::with_variant(memory, Variant::Nmos6502);
+        processor.set_pc(0x1000);
+        processor.enable_history(100);
+        processor
+    }
+
+    #[test]
+    fn step_back_undoes_registers_and_writes() {
+        let mut processor = storing_processor();
+        processor.step(3);
+        let before_store = processor.state();
+        let cycles = processor.cycles();
+        processor.step(1);
+        assert_eq!(processor.memory.read_byte(0x20), 1);
+        assert!(processor.step_back());
+        assert_eq!(processor.memory.read_byte(0x20), 0);
+        assert_eq!(processor.state(), before_store);
+        assert_eq!(processor.cycles(), cycles);
+        // Running forwards again gives the same result
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        assert_eq!(processor.memory.read_bytes(0x20, 4), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn rewind_to_cycle() {
+        let mut processor = storing_processor();
+        let start = processor.cycles();
+        processor.step(5);
+        let middle = processor.cycles();
+        let state = processor.state();
+        assert_eq!(processor.run_until_brk(), StopReason::Brk);
+        // Going back to a cycle partway through an instruction lands on its start
+        assert!(processor.rewind_to_cycle(middle + 1));
+        assert_eq!(processor.state(), state);
+        assert_eq!(processor.memory.read_bytes(0x20, 4), [1, 0, 0, 0]);
+        assert!(processor.rewind_to_cycle(start));
+        assert_eq!(processor.pc(), 0x1000);
+        assert_eq!(processor.history_len(), 0);
+        assert!(!processor.step_back());
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut processor = storing_processor();
+        processor.enable_history(3);
+        processor.step(10);
+        assert_eq!(processor.history_len(), 3);
+        let start = processor.cycles();
+        assert!(!processor.rewind_to_cycle(0));
+        assert!(processor.cycles() < start);
+        assert_eq!(processor.history_len(), 0);
+    }
+
+    #[test]
+    fn step_back_abandons_partly_stepped_instruction() {
+        let mut processor = storing_processor();
+        processor.step(3);
+        let state = processor.state();
+        // STA $1F,X writes on its fourth cycle
+        for _ in 0..4 {
+            processor.step_cycle();
+        }
+        assert!(!processor.is_mid_instruction());
+        assert_eq!(processor.memory.read_byte(0x20), 1);
+        processor.step_back();
+        processor.step_cycle();
+        assert!(processor.is_mid_instruction());
+        assert!(processor.step_back());
+        assert!(!processor.is_mid_instruction());
+        assert_eq!(processor.state(), state);
+        assert_eq!(processor.memory.read_byte(0x20), 0);
+    }
+
+    #[test]
+    fn undoes_io_port_writes() {
+        let mut memory = Memory::new();
+        // LDA #$07; STA $00
+        memory.write_bytes(0x1000, &[0xA9, 0x07, 0x85, 0x00]);
+        let mut processor = Processor::with_variant(memory, Variant::Mos6510);
+        processor.set_pc(0x1000);
+        processor.enable_history(10);
+        processor.step(2);
+        assert_eq!(processor.memory.io_port().unwrap().direction(), 0x07);
+        processor.step_back();
+        assert_eq!(processor.memory.io_port().unwrap().direction(), 0);
+        assert_eq!(processor.memory.read_byte(0x0000), 0);
+    }
+
+    #[test]
+    fn undoes_io_port_data_writes() {
+        let mut memory = Memory::new();
+        memory.attach_io_port(IoPort::c64());
+        // LDA #$05; STA $01
+        memory.write_bytes(0x1000, &[0xA9, 0x05, 0x85, 0x01]);
+        let mut processor = Processor::with_variant(memory, Variant::Mos6510);
+        processor.set_pc(0x1000);
+        processor.enable_history(10);
+        processor.step(1);
+        let io_port = *processor.memory.io_port().unwrap();
+        processor.step(1);
+        assert_ne!(*processor.memory.io_port().unwrap(), io_port);
+        assert!(processor.step_back());
+        assert_eq!(*processor.memory.io_port().unwrap(), io_port);
+        assert_eq!(processor.memory.read_bytes(0x0001, 1), [0]);
+    }
+
+    /// A processor running `program` at $1000, with memory from `mapper` given four 16K banks
+    /// that each hold their own number
+    fn mapped_processor(program: &[u8], mapper: impl FnOnce(Vec<u8>) -> Memory) -> Processor {
+        let prg = (0..4).flat_map(|bank| vec![bank as u8; 0x4000]).collect();
+        let mut memory = mapper(prg);
+        memory.write_bytes(0x1000, program);
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.enable_history(10);
+        processor
+    }
+
+    #[test]
+    fn undoes_uxrom_bank_switches() {
+        // LDA #$01; STA $8000; LDA #$02; STA $8000
+        let program = [0xA9, 0x01, 0x8D, 0x00, 0x80, 0xA9, 0x02, 0x8D, 0x00, 0x80];
+        let mut processor = mapped_processor(&program, |prg| {
+            let mut memory = Memory::new();
+            memory.attach_mapper(UxRom::new(prg));
+            memory
+        });
+        processor.step(4);
+        assert_eq!(processor.memory.mapper::<UxRom>().unwrap().bank(), 2);
+        assert!(processor.step_back());
+        assert_eq!(processor.memory.mapper::<UxRom>().unwrap().bank(), 1);
+        processor.step_back();
+        processor.step_back();
+        assert_eq!(processor.memory.mapper::<UxRom>().unwrap().bank(), 0);
+        assert_eq!(processor.memory.read_byte(0x8000), 0);
+    }
+
+    #[test]
+    fn undoes_mmc1_register_writes() {
+        // LDA #$03, then STA $E000; LSR A five times to load it into the PRG bank register
+        let mut program = vec![0xA9, 0x03];
+        for _ in 0..5 {
+            program.extend_from_slice(&[0x8D, 0x00, 0xE0, 0x4A]);
+        }
+        let mut processor = mapped_processor(&program, |prg| {
+            let mut memory = Memory::new();
+            memory.attach_mapper(Mmc1::new(prg));
+            memory
+        });
+        processor.step(1 + 2 * 4 + 1);
+        assert_eq!(processor.memory.mapper::<Mmc1>().unwrap().prg_bank(), 3);
+        assert_eq!(processor.memory.read_byte(0x8000), 3);
+        // Undoing the last write leaves four bits in the shift register
+        assert!(processor.step_back());
+        assert_eq!(processor.memory.mapper::<Mmc1>().unwrap().prg_bank(), 0);
+        assert_eq!(processor.memory.read_byte(0x8000), 0);
+        processor.step(1);
+        assert_eq!(processor.memory.mapper::<Mmc1>().unwrap().prg_bank(), 3);
+    }
+
+    #[test]
+    fn undoes_open_bus_value() {
+        let mut memory = Memory::new();
+        memory.write_byte(0x20, 0x77);
+        // LDA $20; LDA #$11
+        memory.write_bytes(0x1000, &[0xA5, 0x20, 0xA9, 0x11]);
+        let mut processor = Processor::with_variant(memory, Variant::Nmos6502);
+        processor.set_pc(0x1000);
+        processor.enable_history(10);
+        processor.step(1);
+        assert_eq!(processor.memory.bus_value(), 0x77);
+        processor.step(1);
+        assert_eq!(processor.memory.bus_value(), 0x11);
+        assert!(processor.step_back());
+        assert_eq!(processor.memory.bus_value(), 0x77);
+    }
+}
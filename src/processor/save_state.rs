@@ -0,0 +1,445 @@
+// Save states are a fixed layout of little-endian fields, preceded by a magic number and version.
+// Bump `VERSION` whenever the layout changes
+
+use std::io::{Read, Write};
+
+use super::{CycleStep, Processor};
+use crate::{
+    cycle::{BusAccess, BusCycle},
+    error::SaveStateError,
+    io_port::IoPort,
+    memory::Memory,
+    variant::Variant,
+};
+
+/// Identifies a save state file
+pub const MAGIC: [u8; 4] = *b"M65S";
+/// Version of the layout written by [`Processor::save_state`]
+pub const VERSION: u16 = 3;
+
+// Save states need to see all of `Memory`, so they aren't available for other buses
+impl Processor<Memory> {
+    /// Writes the complete machine state (registers, interrupt lines, cycle counters, any
+    /// partly stepped instruction, all of memory and the mapper's registers) so it can be
+    /// resumed with `from_state`, or with `load_state` if there's a mapper.
+    ///
+    /// The tracer, breakpoints, history, undefined opcode policy and strict mode aren't part of
+    /// the machine, so they aren't saved. Neither are the mapper's ROM images and the ROM
+    /// regions, which come from the cartridge
+    pub fn save_state(&self, writer: &mut impl Write) -> Result<(), SaveStateError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        let variant = Variant::ALL
+            .iter()
+            .position(|&variant| variant == self.variant)
+            .expect("every variant is in Variant::ALL");
+        writer.write_all(&[variant as u8, self.a, self.x, self.y, *self.p.raw(), self.s])?;
+        writer.write_all(&self.pc.to_le_bytes())?;
+        writer.write_all(&[
+            self.irq_line as u8,
+            self.nmi_line as u8,
+            self.nmi_pending as u8,
+            self.irq_disabled_at_poll as u8,
+            self.waiting as u8,
+            self.magic_constant,
+        ])?;
+        write_optional_address(writer, self.jammed)?;
+        writer.write_all(&self.cycles.to_le_bytes())?;
+        match &self.cycle_step {
+            Some(step) => {
+                writer.write_all(&[1])?;
+                write_cycle_step(writer, step)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        writer.write_all(self.memory.ram())?;
+        match self.memory.io_port() {
+            Some(io_port) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&io_port.to_bytes())?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        writer.write_all(&[self.memory.bus_value()])?;
+        match self.memory.attached_mapper() {
+            Some(mapper) => {
+                let state = mapper.save_state();
+                writer.write_all(&[1])?;
+                writer.write_all(&(state.len() as u32).to_le_bytes())?;
+                writer.write_all(&state)?;
+            }
+            None => writer.write_all(&[0])?,
+        }
+        Ok(())
+    }
+    /// Creates a `Processor` from a state written by [`Processor::save_state`]. States saved
+    /// with a mapper attached have to be loaded with `load_state`, as the mapper can't be
+    /// recreated from them
+    pub fn from_state(reader: &mut impl Read) -> Result<Processor, SaveStateError> {
+        match read_state(reader)? {
+            (processor, None) => Ok(processor),
+            (_, Some(_)) => Err(SaveStateError::MapperMismatch),
+        }
+    }
+    /// Replaces the machine state with one written by [`Processor::save_state`], keeping the
+    /// tracer, breakpoints, undefined opcode policy, strict mode, mapper and ROM regions. The
+    /// mapper's registers are restored from the state, so it needs the same kind of mapper
+    /// attached as when it was saved. History is kept enabled, but emptied. Nothing is changed
+    /// if loading fails
+    pub fn load_state(&mut self, reader: &mut impl Read) -> Result<(), SaveStateError> {
+        let (mut loaded, mapper_state) = read_state(reader)?;
+        match (self.memory.attached_mapper_mut(), mapper_state) {
+            (Some(mapper), Some(state)) => {
+                if !mapper.load_state(&state) {
+                    return Err(SaveStateError::MapperMismatch);
+                }
+            }
+            (None, None) => {}
+            _ => return Err(SaveStateError::MapperMismatch),
+        }
+        loaded.tracer = self.tracer.take();
+        loaded.breakpoints = std::mem::take(&mut self.breakpoints);
+        loaded.undefined_opcode_policy = self.undefined_opcode_policy;
+        loaded.strict_rom = self.strict_rom;
+        loaded.memory.take_configuration(&mut self.memory);
+        // History from before the load can't be undone on top of it
+        loaded.history = self.history.as_ref().map(|history| history.cleared());
+        *self = loaded;
+        Ok(())
+    }
+}
+
+/// Reads a state written by [`Processor::save_state`], along with the mapper's state if it had one
+fn read_state(reader: &mut impl Read) -> Result<(Processor, Option<Vec<u8>>), SaveStateError> {
+    if read_array::<4>(reader)? != MAGIC {
+        return Err(SaveStateError::NotASaveState);
+    }
+    let version = u16::from_le_bytes(read_array(reader)?);
+    if version != VERSION {
+        return Err(SaveStateError::UnsupportedVersion(version));
+    }
+    let [variant, a, x, y, p, s] = read_array(reader)?;
+    let variant = *Variant::ALL
+        .get(variant as usize)
+        .ok_or(SaveStateError::Corrupt("unknown variant"))?;
+    let pc = u16::from_le_bytes(read_array(reader)?);
+    let [
+        irq_line,
+        nmi_line,
+        nmi_pending,
+        irq_disabled_at_poll,
+        waiting,
+        magic_constant,
+    ] = read_array(reader)?;
+    let jammed = read_optional_address(reader)?;
+    let cycles = u64::from_le_bytes(read_array(reader)?);
+    let cycle_step = match read_bool(reader)? {
+        true => Some(read_cycle_step(reader)?),
+        false => None,
+    };
+    let mut memory = Memory::new();
+    reader.read_exact(memory.ram_mut())?;
+    if read_bool(reader)? {
+        memory.attach_io_port(IoPort::from_bytes(read_array(reader)?));
+    }
+    let [bus_value] = read_array(reader)?;
+    let mapper_state = match read_bool(reader)? {
+        true => {
+            let length = u32::from_le_bytes(read_array(reader)?) as u64;
+            let mut state = Vec::new();
+            reader.take(length).read_to_end(&mut state)?;
+            if state.len() as u64 != length {
+                return Err(SaveStateError::Corrupt("mapper state cut short"));
+            }
+            Some(state)
+        }
+        false => None,
+    };
+
+    let mut processor = Processor::with_variant(memory, variant);
+    // Powering on runs the reset sequence, which drives the bus
+    processor.memory.set_bus_value(bus_value);
+    processor.a = a;
+    processor.x = x;
+    processor.y = y;
+    *processor.p.raw_mut() = p;
+    processor.s = s;
+    processor.pc = pc;
+    processor.irq_line = flag(irq_line)?;
+    processor.nmi_line = flag(nmi_line)?;
+    processor.nmi_pending = flag(nmi_pending)?;
+    processor.irq_disabled_at_poll = flag(irq_disabled_at_poll)?;
+    processor.waiting = flag(waiting)?;
+    processor.magic_constant = magic_constant;
+    processor.jammed = jammed;
+    processor.cycles = cycles;
+    processor.cycle_step = cycle_step;
+    Ok((processor, mapper_state))
+}
+
+fn write_cycle_step(writer: &mut impl Write, step: &CycleStep) -> Result<(), SaveStateError> {
+    writer.write_all(&[step.a, step.x, step.y, step.p, step.s])?;
+    writer.write_all(&step.pc.to_le_bytes())?;
+    writer.write_all(&[step.irq_disabled_at_poll as u8, step.waiting as u8])?;
+    writer.write_all(&step.cycles.to_le_bytes())?;
+    write_optional_address(writer, step.jammed)?;
+    write_optional_address(writer, step.interrupt)?;
+    // An instruction never takes more than 8 cycles
+    writer.write_all(&[step.completed.len() as u8])?;
+    for cycle in &step.completed {
+        writer.write_all(&cycle.address.to_le_bytes())?;
+        let access = match cycle.access {
+            BusAccess::Read => 0,
+            BusAccess::Write => 1,
+        };
+        writer.write_all(&[cycle.data, access])?;
+    }
+    Ok(())
+}
+
+fn read_cycle_step(reader: &mut impl Read) -> Result<CycleStep, SaveStateError> {
+    let [a, x, y, p, s] = read_array(reader)?;
+    let pc = u16::from_le_bytes(read_array(reader)?);
+    let [irq_disabled_at_poll, waiting] = read_array(reader)?;
+    let cycles = u64::from_le_bytes(read_array(reader)?);
+    let jammed = read_optional_address(reader)?;
+    let interrupt = read_optional_address(reader)?;
+    let [count] = read_array(reader)?;
+    let completed = (0..count)
+        .map(|_| {
+            let address = u16::from_le_bytes(read_array(reader)?);
+            let [data, access] = read_array(reader)?;
+            let access = match access {
+                0 => BusAccess::Read,
+                1 => BusAccess::Write,
+                _ => return Err(SaveStateError::Corrupt("unknown bus access")),
+            };
+            Ok(BusCycle {
+                address,
+                data,
+                access,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(CycleStep {
+        a,
+        x,
+        y,
+        p,
+        s,
+        pc,
+        irq_disabled_at_poll: flag(irq_disabled_at_poll)?,
+        cycles,
+        jammed,
+        waiting: flag(waiting)?,
+        interrupt,
+        completed,
+    })
+}
+
+fn write_optional_address(
+    writer: &mut impl Write,
+    address: Option<u16>,
+) -> Result<(), SaveStateError> {
+    match address {
+        Some(address) => {
+            writer.write_all(&[1])?;
+            writer.write_all(&address.to_le_bytes())?;
+        }
+        None => writer.write_all(&[0, 0, 0])?,
+    }
+    Ok(())
+}
+
+fn read_optional_address(reader: &mut impl Read) -> Result<Option<u16>, SaveStateError> {
+    let present = read_bool(reader)?;
+    let address = u16::from_le_bytes(read_array(reader)?);
+    Ok(present.then_some(address))
+}
+
+fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], SaveStateError> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_bool(reader: &mut impl Read) -> Result<bool, SaveStateError> {
+    let [byte] = read_array(reader)?;
+    flag(byte)
+}
+
+fn flag(byte: u8) -> Result<bool, SaveStateError> {
+    match byte {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(SaveStateError::Corrupt("boolean out of range")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        banking::{Mmc1, UxRom},
+        processor::StopReason,
+    };
+
+    fn saved(processor: &Processor) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        processor.save_state(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Counts down from 5 in X, adding 7 to A each time, then BRKs
+    fn counting_processor(variant: Variant) -> Processor {
+        let mut memory = Memory::new();
+        // LDX #$05; LDA #$00; CLC; loop: ADC #$07; DEX; BNE loop; STA $20
+        memory.write_bytes(
+            0x1000,
+            &[
+                0xA2, 0x05, 0xA9, 0x00, 0x18, 0x69, 0x07, 0xCA, 0xD0, 0xFB, 0x85, 0x20,
+            ],
+        );
+        let mut processor = Processor::with_variant(memory, variant);
+        processor.pc = 0x1000;
+        processor
+    }
+
+    #[test]
+    fn resumes_identically() {
+        for variant in Variant::ALL {
+            let mut original = counting_processor(variant);
+            original.step(5);
+            original.set_irq_line(true);
+            original.set_p(original.p() | 0b00000100);
+            let mut restored = Processor::from_state(&mut saved(&original).as_slice()).unwrap();
+            assert_eq!(restored.state(), original.state());
+            assert_eq!(restored.cycles(), original.cycles());
+            assert_eq!(original.run_until_brk(), StopReason::Brk);
+            assert_eq!(restored.run_until_brk(), StopReason::Brk);
+            assert_eq!(restored.state(), original.state());
+            assert_eq!(restored.cycles(), original.cycles());
+            assert_eq!(saved(&restored), saved(&original), "{:?}", variant);
+        }
+    }
+
+    #[test]
+    fn resumes_mid_instruction() {
+        let mut original = counting_processor(Variant::Nmos6502);
+        original.step(4);
+        original.step_cycle();
+        assert!(original.is_mid_instruction());
+        let mut restored = Processor::from_state(&mut saved(&original).as_slice()).unwrap();
+        assert!(restored.is_mid_instruction());
+        while original.is_mid_instruction() {
+            assert_eq!(restored.step_cycle(), original.step_cycle());
+        }
+        assert!(!restored.is_mid_instruction());
+        assert_eq!(restored.state(), original.state());
+    }
+
+    #[test]
+    fn keeps_jam_and_io_port() {
+        let mut memory = Memory::new();
+        memory.write_bytes(0x1000, &[0x02]);
+        let mut original = Processor::with_variant(memory, Variant::Mos6510);
+        original.pc = 0x1000;
+        original
+            .memory
+            .io_port_mut()
+            .unwrap()
+            .set_input(0b00010000, 0);
+        let _ = original.step(1);
+        let restored = Processor::from_state(&mut saved(&original).as_slice()).unwrap();
+        assert!(restored.is_jammed());
+        assert_eq!(restored.memory.io_port(), original.memory.io_port());
+    }
+
+    #[test]
+    fn restored_state_saves_identically() {
+        let mut memory = Memory::new();
+        memory.mark_unmapped(0x4000..=0x5FFF);
+        memory.write_byte(0x20, 0x77);
+        // LDA $20
+        memory.write_bytes(0x1000, &[0xA5, 0x20]);
+        let mut original = Processor::with_start_address(memory, 0x1000);
+        original.step(1);
+        assert_eq!(original.memory.bus_value(), 0x77);
+        let bytes = saved(&original);
+        let mut restored = Processor::from_state(&mut bytes.as_slice()).unwrap();
+        assert_eq!(saved(&restored), bytes);
+        restored.memory.mark_unmapped(0x4000..=0x5FFF);
+        assert_eq!(restored.memory.read_byte(0x4000), 0x77);
+    }
+
+    #[test]
+    fn load_keeps_breakpoints() {
+        let original = counting_processor(Variant::Nmos6502);
+        let bytes = saved(&original);
+        let mut processor = counting_processor(Variant::Nmos6502);
+        processor.add_breakpoint(0x1007);
+        processor.step(3);
+        processor.load_state(&mut bytes.as_slice()).unwrap();
+        assert_eq!(processor.pc(), 0x1000);
+        assert_eq!(processor.run_until_brk(), StopReason::Breakpoint(0x1007));
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        let bytes = saved(&counting_processor(Variant::Nmos6502));
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(matches!(
+            Processor::from_state(&mut wrong_magic.as_slice()),
+            Err(SaveStateError::NotASaveState)
+        ));
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            Processor::from_state(&mut newer.as_slice()),
+            Err(SaveStateError::UnsupportedVersion(version)) if version == VERSION + 1
+        ));
+        assert!(matches!(
+            Processor::from_state(&mut &bytes[..100]),
+            Err(SaveStateError::Io(_))
+        ));
+        // Loading a bad file leaves the processor alone
+        let mut processor = counting_processor(Variant::Nmos6502);
+        processor.step(2);
+        let state = processor.state();
+        assert!(processor.load_state(&mut &bytes[..100]).is_err());
+        assert_eq!(processor.state(), state);
+    }
+
+    #[test]
+    fn restores_mapper_registers() {
+        let prg = (0..4).flat_map(|bank| vec![bank as u8; 0x4000]).collect();
+        let mut memory = Memory::new();
+        memory.attach_mapper(UxRom::new(prg));
+        let mut processor = Processor::with_start_address(memory, 0x1000);
+        processor.memory.write_byte(0x8000, 1);
+        let bytes = saved(&processor);
+        processor.memory.write_byte(0x8000, 2);
+        processor.load_state(&mut bytes.as_slice()).unwrap();
+        assert_eq!(processor.memory.read_byte(0x8000), 1);
+        assert_eq!(saved(&processor), bytes);
+        // The mapper can't be recreated without its ROM
+        assert!(matches!(
+            Processor::from_state(&mut bytes.as_slice()),
+            Err(SaveStateError::MapperMismatch)
+        ));
+        let mut unmapped = counting_processor(Variant::Nmos6502);
+        assert!(matches!(
+            unmapped.load_state(&mut bytes.as_slice()),
+            Err(SaveStateError::MapperMismatch)
+        ));
+        // Nor can a different mapper take its state
+        processor.memory.attach_mapper(Mmc1::new(vec![0; 0x8000]));
+        let state = processor.state();
+        assert!(matches!(
+            processor.load_state(&mut bytes.as_slice()),
+            Err(SaveStateError::MapperMismatch)
+        ));
+        assert_eq!(processor.state(), state);
+    }
+}
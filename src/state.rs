@@ -0,0 +1,49 @@
+use std::fmt::{self, Display};
+
+use crate::flags::Flags;
+
+/// Snapshot of the processor's registers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuState {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    /// Status register, as stored in the processor
+    pub p: u8,
+    pub s: u8,
+    pub pc: u16,
+}
+
+impl Display for CpuState {
+    /// Shows the registers in hex, with the flags as `NV-BDIZC`. Set flags are in upper case
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "PC:{:04X} A:{:02X} X:{:02X} Y:{:02X} S:{:02X} P:{}",
+            self.pc,
+            self.a,
+            self.x,
+            self.y,
+            self.s,
+            Flags::from(self.p)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_shows_flags_by_case() {
+        let state = CpuState {
+            a: 0x05,
+            x: 0xFF,
+            y: 0x00,
+            p: 0b10100101,
+            s: 0xFD,
+            pc: 0x1000,
+        };
+        assert_eq!(state.to_string(), "PC:1000 A:05 X:FF Y:00 S:FD P:Nv-bdIzC");
+    }
+}
@@ -0,0 +1,38 @@
+use std::fmt::{self, Debug};
+
+use crate::{addressing::AddressingMode, instructions::Instruction, state::CpuState};
+
+/// Everything known about an instruction when a `Tracer` hook is called
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceEvent {
+    /// Address the opcode was fetched from
+    pub pc: u16,
+    pub opcode: u8,
+    pub instruction: Instruction,
+    pub addressing_mode: AddressingMode,
+    /// Address of the operand, or the jump or branch target. Only known once the instruction
+    /// has run, so always `None` for `before_instruction`, as it is for instructions without
+    /// one, like implied and accumulator instructions
+    pub effective_address: Option<u16>,
+    /// Registers before the instruction for `before_instruction`, and after it for
+    /// `after_instruction`
+    pub state: CpuState,
+}
+
+/// Observes every instruction run by a `Processor`.
+///
+/// Each hook is called once per instruction, even when it's stepped a cycle at a time.
+/// Interrupt sequences aren't traced
+pub trait Tracer {
+    /// Called once the opcode has been fetched, before the instruction reads its operands or
+    /// writes anything
+    fn before_instruction(&mut self, _event: &TraceEvent) {}
+    /// Called once the instruction has finished
+    fn after_instruction(&mut self, _event: &TraceEvent) {}
+}
+
+impl Debug for dyn Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Tracer")
+    }
+}
@@ -0,0 +1,88 @@
+/// The model of 6502 being emulated, chosen when a `Processor` is created
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    /// The original MOS 6502, including its undocumented opcodes and bugs
+    #[default]
+    Nmos6502,
+    /// The WDC 65C02, with the Rockwell bit instructions and WAI/STP.
+    /// Fixes the JMP indirect page wrap bug, clears D on interrupts and has valid decimal flags
+    Wdc65C02,
+    /// The NES's CPU. An NMOS 6502 with its decimal mode disconnected, so the D flag can
+    /// still be set but ADC and SBC are always binary
+    Ricoh2A03,
+    /// The C64's CPU. An NMOS 6502 with an I/O port at $0000 and $0001
+    Mos6510,
+}
+
+impl Variant {
+    /// Every variant, for checking behaviour across all of them
+    pub const ALL: [Variant; 4] = [
+        Variant::Nmos6502,
+        Variant::Wdc65C02,
+        Variant::Ricoh2A03,
+        Variant::Mos6510,
+    ];
+    /// Whether this is a CMOS part, which adds instructions and changes the timing and
+    /// dummy accesses of some existing ones
+    pub fn is_cmos(self) -> bool {
+        matches!(self, Variant::Wdc65C02)
+    }
+    /// Whether the D flag makes ADC and SBC work in binary-coded decimal
+    pub fn has_decimal_mode(self) -> bool {
+        !matches!(self, Variant::Ricoh2A03)
+    }
+    /// Whether undefined opcodes run the NMOS undocumented instructions (including JAM).
+    /// On CMOS parts they are all NOPs
+    pub fn has_undocumented_opcodes(self) -> bool {
+        !self.is_cmos()
+    }
+    /// Whether JMP ($xxFF) reads the high byte of its target from $xx00
+    pub fn has_indirect_jump_bug(self) -> bool {
+        !self.is_cmos()
+    }
+    /// Whether there's an I/O port on the chip, mapped over $0000 and $0001
+    pub fn has_io_port(self) -> bool {
+        matches!(self, Variant::Mos6510)
+    }
+    /// Whether interrupts, BRK and RESET clear the D flag
+    pub fn clears_decimal_on_interrupt(self) -> bool {
+        self.is_cmos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nmos_family_shares_quirks() {
+        for variant in [Variant::Nmos6502, Variant::Ricoh2A03, Variant::Mos6510] {
+            assert!(!variant.is_cmos());
+            assert!(variant.has_undocumented_opcodes());
+            assert!(variant.has_indirect_jump_bug());
+            assert!(!variant.clears_decimal_on_interrupt());
+        }
+    }
+
+    #[test]
+    fn only_2a03_lacks_decimal_mode() {
+        for variant in Variant::ALL {
+            assert_eq!(variant.has_decimal_mode(), variant != Variant::Ricoh2A03);
+        }
+    }
+
+    #[test]
+    fn only_6510_has_io_port() {
+        for variant in Variant::ALL {
+            assert_eq!(variant.has_io_port(), variant == Variant::Mos6510);
+        }
+    }
+
+    #[test]
+    fn cmos_quirks() {
+        let variant = Variant::Wdc65C02;
+        assert!(!variant.has_undocumented_opcodes());
+        assert!(!variant.has_indirect_jump_bug());
+        assert!(variant.clears_decimal_on_interrupt());
+    }
+}
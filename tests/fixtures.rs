@@ -1,6 +1,9 @@
 use std::{fs, path::PathBuf};
 
-use emulator::{memory::Memory, processor::Processor};
+use emulator::{
+    memory::Memory,
+    processor::{Processor, StopReason},
+};
 use testing::fixture;
 
 #[fixture("tests/fixtures/**/test.bin")]
@@ -8,11 +11,8 @@ fn fixture_tests(input: PathBuf) {
     let program = fs::read(&input).unwrap();
     let mut memory = Memory::new();
     memory.write_bytes(0x1000, &program);
-    let mut processor = Processor::new(memory);
-    // Using 0 byte for program termination for now (which corresponds to the BRK instruction)
-    while processor.peek_byte_at_pc() != 0 {
-        processor.process_next_instruction();
-    }
+    let mut processor = Processor::with_start_address(memory, 0x1000);
+    assert_eq!(processor.run_until_brk(), StopReason::Brk);
     let start_region = 0x1000;
     let num_bytes_to_read = 1000;
     let expected_memory_path = input.parent().unwrap().join("expected_memory.bin");